@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use crate::{color::Color, hittable::HitRecord, material::Material, ray::Ray, texture::{SolidColor, Texture}};
+
+/// Which part of the mask texture gives the opacity
+#[derive(Clone, Copy)]
+pub enum MaskChannel {
+	// The texture's own alpha, e.g a PNG with transparency
+	Alpha,
+	// The average of its color channels, for black and white masks
+	Gray,
+}
+
+/// Cuts holes into any material with an opacity mask, e.g a leaf or a decal on a quad.
+/// Where the mask is 0 rays go through as if the surface wasn't there, in between they do so randomly that often.
+pub struct AlphaMasked {
+	material: Arc<dyn Material>,
+	mask: Arc<dyn Texture>,
+	channel: MaskChannel,
+}
+
+impl AlphaMasked {
+	pub fn new(material: Arc<dyn Material>, mask: Arc<dyn Texture>, channel: MaskChannel) -> Self {
+		Self { material, mask, channel }
+	}
+
+	/// The same opacity everywhere, e.g for a half see-through screen door effect
+	pub fn from_value(material: Arc<dyn Material>, alpha: f64) -> Self {
+		Self::new(material, Arc::new(SolidColor::from_rgb(alpha, alpha, alpha)), MaskChannel::Gray)
+	}
+}
+
+impl Material for AlphaMasked {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		self.material.scatter(r_in, rec, attenuation, scattered)
+	}
+
+	fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+		self.material.emitted(r_in, rec)
+	}
+
+	fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		self.material.eval(r_in, rec, scattered)
+	}
+
+	fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+		self.material.scattering_pdf(r_in, rec, scattered)
+	}
+
+	fn alpha(&self, rec: &HitRecord) -> f64 {
+		let mask = match self.channel {
+			MaskChannel::Alpha => self.mask.alpha(rec.u, rec.v, &rec.p),
+			MaskChannel::Gray => {
+				let c = self.mask.value(rec.u, rec.v, &rec.p);
+				(c.x() + c.y() + c.z())/3.0
+			}
+		};
+		// Both the mask and whatever the wrapped material cuts out itself
+		mask.clamp(0.0, 1.0) * self.material.alpha(rec)
+	}
+}
@@ -0,0 +1,395 @@
+use std::f64::consts::PI;
+
+use crate::{camera::Camera, color::Color, film::Film, hittable::{HitRecord, Hittable}, integrator::{escaped_radiance, sample_delta_lights, sample_sun, Integrator, PathTracer, T_MIN}, interval::Interval, onb::Onb, ray::Ray, scene::Scene, vec3::{random_cosine_direction, Point3, Vec3}};
+
+/// Bidirectional path tracing: a subpath is traced from the camera and another from a light,
+/// then every prefix of one is connected to every prefix of the other. Each of those strategies
+/// is good at different kinds of paths (e.g light tracing finds caustics seen through glass),
+/// so they are combined with multiple importance sampling using the balance heuristic.
+///
+/// Connections straight to the camera land on arbitrary pixels and are splatted onto the film.
+/// Only emitters in `Scene::lights` can start light subpaths, the sky is only reached from the camera.
+#[derive(Default)]
+pub struct Bidirectional;
+
+impl Bidirectional {
+	pub fn new() -> Self {
+		Self
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+	Camera,
+	Light,
+	Surface
+}
+
+#[derive(Clone)]
+struct Vertex {
+	kind: VertexKind,
+	// Position, normal and material. For the camera only the position is meaningful
+	rec: HitRecord,
+	// The ray that reached this vertex while its subpath was traced
+	r_in: Ray,
+	// Throughput of the subpath up to this vertex
+	beta: Color,
+	// Whether scattering at this vertex picked a specular (delta) direction
+	delta: bool,
+	// Area density of this vertex having been generated by its own subpath (fwd) or by the opposite one (rev)
+	pdf_fwd: f64,
+	pdf_rev: f64,
+}
+
+impl Vertex {
+	fn camera(p: &Point3, beta: Color) -> Self {
+		let mut rec = HitRecord::new();
+		rec.p = *p;
+		Self { kind: VertexKind::Camera, rec, r_in: Ray::new(), beta, delta: false, pdf_fwd: 0.0, pdf_rev: 0.0 }
+	}
+
+	fn light(rec: HitRecord, beta: Color, pdf: f64) -> Self {
+		Self { kind: VertexKind::Light, rec, r_in: Ray::new(), beta, delta: false, pdf_fwd: pdf, pdf_rev: 0.0 }
+	}
+
+	fn surface(rec: HitRecord, r_in: Ray, beta: Color) -> Self {
+		Self { kind: VertexKind::Surface, rec, r_in, beta, delta: false, pdf_fwd: 0.0, pdf_rev: 0.0 }
+	}
+
+	fn p(&self) -> Point3 {
+		self.rec.p
+	}
+
+	/// Converts a solid angle density of leaving this vertex towards `next` into an area density at `next`
+	fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+		let w = next.p() - self.p();
+		let distance_squared = w.length_squared();
+		if distance_squared == 0.0 {
+			return 0.0
+		}
+
+		let mut pdf = pdf/distance_squared;
+		if next.kind != VertexKind::Camera {
+			pdf *= Vec3::dot(&next.rec.normal, &(w/distance_squared.sqrt())).abs();
+		}
+		pdf
+	}
+
+	/// The BSDF times cosine for light travelling along this vertex's subpath and leaving towards `next`
+	fn eval(&self, next: &Vertex) -> Color {
+		self.rec.mat.eval(&self.r_in, &self.rec, &ray_between(&self.p(), &next.p()))
+	}
+
+	/// Area density at `next` of having scattered towards it from this vertex, after arriving from `prev`
+	fn pdf(&self, cam: &Camera, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+		let to_next = ray_between(&self.p(), &next.p());
+		let pdf = match self.kind {
+			VertexKind::Camera => cam.pdf_direction(&to_next.direction()),
+			VertexKind::Light => return self.pdf_light(next),
+			VertexKind::Surface => {
+				let r_in = match prev {
+					Some(prev) => ray_between(&prev.p(), &self.p()),
+					None => self.r_in
+				};
+				self.rec.mat.scattering_pdf(&r_in, &self.rec, &to_next)
+			}
+		};
+
+		self.convert_density(pdf, next)
+	}
+
+	/// Area density at `next` of this vertex, lying on an emitter, having sent light towards it
+	fn pdf_light(&self, next: &Vertex) -> f64 {
+		let direction = Vec3::unit_vector(&(next.p() - self.p()));
+		let cos_theta = Vec3::dot(&self.rec.normal, &direction);
+		self.convert_density(f64::max(0.0, cos_theta/PI), next)
+	}
+
+	/// Area density of a light subpath having started at this vertex
+	fn pdf_light_origin(&self, scene: &Scene) -> f64 {
+		scene.lights.surface_pdf(&self.p())
+	}
+}
+
+impl Integrator for Bidirectional {
+	/// Without a camera to connect to, falls back to plain path tracing
+	fn ray_color(&self, r: &Ray, depth: i32, scene: &Scene) -> Color {
+		PathTracer::new().ray_color(r, depth, scene)
+	}
+
+	fn sample_pixel(&self, cam: &Camera, i: i32, j: i32, scene: &Scene, film: &Film) -> Color {
+		let max_depth = cam.max_depth;
+
+		let mut camera_path = Vec::new();
+		let r = cam.get_ray(i, j);
+		camera_path.push(Vertex::camera(&r.origin(), Color::from_xyz(1.0, 1.0, 1.0)));
+		// The sky can't be sampled from the light side, so whatever the camera path sees of it counts in full
+		let mut l = random_walk(scene, r, Color::from_xyz(1.0, 1.0, 1.0), cam.pdf_direction(&r.direction()), max_depth + 1, &mut camera_path, true);
+
+		let mut light_path = Vec::new();
+		light_subpath(scene, max_depth, &mut light_path);
+
+		for t in 1..=camera_path.len() {
+			for s in 0..=light_path.len() {
+				let depth = (s + t) as i32 - 2;
+				if (s == 1 && t == 1) || depth < 0 || depth > max_depth {
+					continue
+				}
+
+				if t == 1 {
+					if let Some((contribution, i, j)) = connect_to_camera(scene, cam, &light_path, s) {
+						film.add_splat(i, j, contribution);
+					}
+				} else {
+					l += connect(scene, cam, &light_path, &camera_path, s, t);
+				}
+			}
+		}
+
+		// Delta lights and the sun can't start light subpaths, shadow rays from the camera subpath are the way to reach them
+		for pt in camera_path.iter().skip(1).take(max_depth.max(0) as usize) {
+			l += pt.beta * (sample_delta_lights(&pt.r_in, &pt.rec, scene) + sample_sun(&pt.r_in, &pt.rec, scene));
+		}
+
+		l
+	}
+}
+
+fn ray_between(from: &Point3, to: &Point3) -> Ray {
+	Ray::from_values(from, &(*to - *from))
+}
+
+fn is_black(c: &Color) -> bool {
+	c.x() == 0.0 && c.y() == 0.0 && c.z() == 0.0
+}
+
+/// Whether nothing blocks the straight line between two points
+fn unoccluded(scene: &Scene, from: &Point3, to: &Point3) -> bool {
+	let w = *to - *from;
+	let distance = w.length();
+	let mut rec = HitRecord::new();
+	!scene.world.hit(&Ray::from_values(from, &(w/distance)), &Interval::from_values(T_MIN, distance - T_MIN), &mut rec)
+}
+
+/// Extends `path` by following scattered rays starting with `r`, adding at most `max_vertices` surface vertices.
+/// For camera subpaths, returns the light picked up from the background if the path escapes.
+fn random_walk(scene: &Scene, r: Ray, beta: Color, pdf: f64, max_vertices: i32, path: &mut Vec<Vertex>, from_camera: bool) -> Color {
+	let mut r = r;
+	let mut beta = beta;
+	let mut pdf_fwd = pdf;
+	let mut vertices = 0;
+
+	while vertices < max_vertices {
+		let mut rec = HitRecord::new();
+		if !scene.world.hit(&r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			if from_camera {
+				// The camera ray sees the sun in full, pdf_fwd is already 0 after specular bounces
+				let bsdf_pdf = if vertices == 0 {0.0} else {pdf_fwd};
+				return beta * escaped_radiance(&r, bsdf_pdf, scene)
+			}
+			break
+		}
+
+		let prev = path.len() - 1;
+		let mut vertex = Vertex::surface(rec.clone(), r, beta);
+		vertex.pdf_fwd = path[prev].convert_density(pdf_fwd, &vertex);
+		path.push(vertex);
+		vertices += 1;
+
+		let mut scattered = Ray::new();
+		let mut attenuation = Color::new();
+		if !rec.mat.scatter(&r, &rec, &mut attenuation, &mut scattered) {
+			break
+		}
+
+		pdf_fwd = rec.mat.scattering_pdf(&r, &rec, &scattered);
+		let mut pdf_rev = 0.0;
+		if pdf_fwd <= 0.0 {
+			path[prev + 1].delta = true;
+			pdf_fwd = 0.0;
+		} else {
+			// density of the walk having gone the other way through this vertex
+			let reversed_in = Ray::from_values(&(rec.p + scattered.direction()), &-scattered.direction());
+			let reversed_out = Ray::from_values(&rec.p, &-r.direction());
+			pdf_rev = rec.mat.scattering_pdf(&reversed_in, &rec, &reversed_out);
+		}
+
+		beta = beta * attenuation;
+		path[prev].pdf_rev = path[prev + 1].convert_density(pdf_rev, &path[prev]);
+		r = scattered;
+	}
+
+	Color::new()
+}
+
+/// Starts a subpath on a random point of a random light and follows it into the scene
+fn light_subpath(scene: &Scene, max_vertices: i32, path: &mut Vec<Vertex>) {
+	let mut rec = HitRecord::new();
+	let pdf_pos = scene.lights.sample_surface(&mut rec);
+	if pdf_pos <= 0.0 {
+		return
+	}
+
+	// Diffuse emitters send light out in a cosine distribution around their normal
+	let direction = Onb::new(&rec.normal).transform(&random_cosine_direction());
+	let cos_theta = Vec3::dot(&rec.normal, &Vec3::unit_vector(&direction));
+	let pdf_dir = cos_theta/PI;
+	let le = rec.mat.emitted(&Ray::from_values(&(rec.p + direction), &-direction), &rec);
+	if pdf_dir <= 0.0 || is_black(&le) {
+		return
+	}
+
+	let r = Ray::from_values(&rec.p, &direction);
+	let beta = le * cos_theta/(pdf_pos * pdf_dir);
+	path.push(Vertex::light(rec, le/pdf_pos, pdf_pos));
+	random_walk(scene, r, beta, pdf_dir, max_vertices, path, false);
+}
+
+/// Strategies with t == 1: connects the end of the light subpath straight to the lens.
+/// Returns the weighted contribution along with the pixel it lands on.
+fn connect_to_camera(scene: &Scene, cam: &Camera, light_path: &[Vertex], s: usize) -> Option<(Color, i32, i32)> {
+	let qs = &light_path[s - 1];
+	if qs.delta || qs.kind != VertexKind::Surface {
+		return None
+	}
+
+	let sample = cam.sample_importance(&qs.p())?;
+	let camera_vertex = Vertex::camera(&sample.lens_point, Color::from_xyz(1.0, 1.0, 1.0) * (sample.importance/sample.pdf));
+	let l = qs.beta * qs.eval(&camera_vertex) * camera_vertex.beta;
+	if is_black(&l) || !unoccluded(scene, &qs.p(), &sample.lens_point) {
+		return None
+	}
+
+	let weight = mis_weight(scene, cam, light_path, &[], Some(&camera_vertex), s, 1);
+	Some((l * weight, sample.i, sample.j))
+}
+
+/// Strategies with t >= 2: connects the first `s` light vertices to the first `t` camera vertices
+fn connect(scene: &Scene, cam: &Camera, light_path: &[Vertex], camera_path: &[Vertex], s: usize, t: usize) -> Color {
+	let pt = &camera_path[t - 1];
+	let mut sampled = None;
+
+	let l = if s == 0 {
+		// the camera path ran into an emitter by itself
+		pt.beta * pt.rec.mat.emitted(&pt.r_in, &pt.rec)
+	} else if s == 1 {
+		// next event estimation, a fresh point is picked on a light
+		if pt.delta {
+			return Color::new()
+		}
+
+		let mut light_rec = HitRecord::new();
+		let pdf = scene.lights.sample_surface(&mut light_rec);
+		if pdf <= 0.0 {
+			return Color::new()
+		}
+
+		let to_light = ray_between(&pt.p(), &light_rec.p);
+		let outward_normal = light_rec.normal;
+		light_rec.set_face_normal(&to_light, &outward_normal);
+		let le = light_rec.mat.emitted(&to_light, &light_rec);
+
+		let distance_squared = to_light.direction().length_squared();
+		let cos_light = Vec3::dot(&outward_normal, &Vec3::unit_vector(&to_light.direction())).abs();
+		let light_vertex = Vertex::light(light_rec, le/pdf, pdf);
+		let l = pt.beta * pt.eval(&light_vertex) * light_vertex.beta * cos_light/distance_squared;
+		if is_black(&l) || !unoccluded(scene, &pt.p(), &light_vertex.p()) {
+			return Color::new()
+		}
+
+		sampled = Some(light_vertex);
+		l
+	} else {
+		let qs = &light_path[s - 1];
+		if pt.delta || qs.delta {
+			return Color::new()
+		}
+
+		let distance_squared = (qs.p() - pt.p()).length_squared();
+		let l = qs.beta * qs.eval(pt) * pt.eval(qs) * pt.beta/distance_squared;
+		if is_black(&l) || !unoccluded(scene, &pt.p(), &qs.p()) {
+			return Color::new()
+		}
+		l
+	};
+
+	if is_black(&l) {
+		return Color::new()
+	}
+
+	l * mis_weight(scene, cam, light_path, camera_path, sampled.as_ref(), s, t)
+}
+
+/// Balance heuristic weight of strategy (s, t) against every other way of sampling the same path.
+/// `sampled` is the freshly sampled endpoint that replaces the end of the light (s == 1) or camera (t == 1) subpath.
+fn mis_weight(scene: &Scene, cam: &Camera, light_path: &[Vertex], camera_path: &[Vertex], sampled: Option<&Vertex>, s: usize, t: usize) -> f64 {
+	if s + t == 2 {
+		return 1.0
+	}
+
+	let pt = if t == 1 {sampled.unwrap()} else {&camera_path[t - 1]};
+	let pt_minus = if t > 1 {Some(&camera_path[t - 2])} else {None};
+	let qs = if s == 1 {sampled} else if s > 1 {Some(&light_path[s - 1])} else {None};
+	let qs_minus = if s > 1 {Some(&light_path[s - 2])} else {None};
+
+	// Only the densities are needed, so work on (pdf_fwd, pdf_rev, delta) copies of both subpaths
+	let mut camera: Vec<(f64, f64, bool)> = camera_path.iter().take(t).map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+	let mut light: Vec<(f64, f64, bool)> = light_path.iter().take(s).map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+	if t == 1 {
+		camera = vec![(pt.pdf_fwd, pt.pdf_rev, pt.delta)];
+	}
+	if let (1, Some(qs)) = (s, qs) {
+		light[0] = (qs.pdf_fwd, qs.pdf_rev, qs.delta);
+	}
+
+	// Connected endpoints aren't delta, or they could not have been connected
+	camera[t - 1].2 = false;
+	if s > 0 {
+		light[s - 1].2 = false;
+	}
+
+	// Reverse densities at and next to the connection, as if each side had generated the other's vertices
+	camera[t - 1].1 = match qs {
+		Some(qs) => qs.pdf(cam, qs_minus, pt),
+		None => pt.pdf_light_origin(scene)
+	};
+	if s == 0 && camera[t - 1].1 <= 0.0 {
+		// an emitter that isn't in the light list, so no other strategy can find this path
+		return 1.0
+	}
+	if let Some(pt_minus) = pt_minus {
+		camera[t - 2].1 = match qs {
+			Some(qs) => pt.pdf(cam, Some(qs), pt_minus),
+			None => pt.pdf_light(pt_minus)
+		};
+	}
+	if let Some(qs) = qs {
+		light[s - 1].1 = pt.pdf(cam, pt_minus, qs);
+	}
+	if let (Some(qs), Some(qs_minus)) = (qs, qs_minus) {
+		light[s - 2].1 = qs.pdf(cam, Some(pt), qs_minus);
+	}
+
+	// Delta densities are stored as zero, they cancel out so treat them as one
+	let remap = |pdf: f64| if pdf != 0.0 {pdf} else {1.0};
+
+	let mut sum_ri = 0.0;
+	let mut ri = 1.0;
+	for i in (1..t).rev() {
+		ri *= remap(camera[i].1)/remap(camera[i].0);
+		if !camera[i].2 && !camera[i - 1].2 {
+			sum_ri += ri;
+		}
+	}
+
+	ri = 1.0;
+	for i in (0..s).rev() {
+		ri *= remap(light[i].1)/remap(light[i].0);
+		let delta_light_vertex = if i > 0 {light[i - 1].2} else {false};
+		if !light[i].2 && !delta_light_vertex {
+			sum_ri += ri;
+		}
+	}
+
+	1.0/(1.0 + sum_ri)
+}
@@ -1,7 +1,7 @@
-use std::{f64::consts::PI, io::{self, Write}, sync::{atomic::AtomicI32, Arc, Mutex}, thread};
+use std::{f64::consts::PI, sync::atomic::AtomicI32};
 use rayon::prelude::*;
 
-use crate::{color::{write_color, Color}, hittable::{HitRecord, Hittable}, interval::Interval, rand::random_f64, ray::Ray, vec3::{random_in_unit_disk, Point3, Vec3}};
+use crate::{color::Color, film::Film, integrator::Integrator, rand::random_f64, ray::Ray, scene::Scene, vec3::{random_in_unit_disk, Point3, Vec3}};
 
 #[derive(Default)]
 pub struct Camera {
@@ -40,7 +40,7 @@ impl Camera {
 		c
 	}
 	
-	pub fn render(&mut self, world: &dyn Hittable) {
+	pub fn render(&mut self, scene: &Scene, integrator: &dyn Integrator) {
 		self.initialize();
 		let Self {
 			image_height,
@@ -54,11 +54,10 @@ impl Camera {
 		
     // Render
 		
-    println!("P3\n{} {}\n255", image_width, image_height);
+		let film = Film::new(image_width, image_height);
+		integrator.preprocess(scene, self);
+
 		let count = AtomicI32::new(0);
-		let all = vec![vec![String::new(); image_width as usize]; image_height as usize];
-		let arc = Arc::new(Mutex::new(all));
-		let arc_clone = Arc::clone(&arc);
 
     (0..image_height).into_par_iter().for_each(|j| {
 			let v = count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
@@ -68,27 +67,15 @@ impl Camera {
 				let mut pixel_color = Color::new();
 
 				for _sample in 0..self.samples_per_pixel {
-					let r = self.get_ray(i, j);
-					pixel_color += Self::ray_color(&r, self.max_depth, world);
+					pixel_color += integrator.sample_pixel(self, i, j, scene, &film);
 				}
 
-				arc_clone.lock().unwrap()[j as usize][i as usize] = write_color(&(self.pixel_samples_scale * pixel_color));
+				film.set_pixel(i, j, self.pixel_samples_scale * pixel_color);
 			}
     });
 
-		let e = arc_clone.lock().unwrap();
-
-		let stdout = io::stdout();
-		let lock = stdout.lock();
-		let mut w = io::BufWriter::new(lock);
-
-		for i in 0..image_height {
-			for j in 0..image_width {
-				writeln!(&mut w, "{}", e[i as usize][j as usize]).unwrap();
-			}
-		}
-
-		io::stdout().flush();
+		// Every pixel sample may have splatted, so splats are averaged over the same sample count
+		film.write_ppm(self.pixel_samples_scale);
 
 		eprint!("\rDone         ");
 	}
@@ -138,19 +125,24 @@ impl Camera {
 
 	}
 
-	fn get_ray(&self, i: i32, j: i32) -> Ray {
+	pub fn get_ray(&self, i: i32, j: i32) -> Ray {
 		// Construct a camera ray originating from the defocus disk and directed at a randomly
 		// sampled point around the pixel location i, j.
 
 		let offset = self.sample_square();
+		self.get_ray_at(i as f64 + offset.x(), j as f64 + offset.y())
+	}
+
+	/// A camera ray through a point on the image given in pixels, where pixel i, j is centered on (i, j).
+	/// For integrators that look at several nearby points on the image at once, e.g to find edges.
+	pub fn get_ray_at(&self, x: f64, y: f64) -> Ray {
 		let pixel_sample = self.pixel00_loc
-			+ ((i as f64 + offset.x()) * self.pixel_delta_u)
-			+ ((j as f64 + offset.y()) * self.pixel_delta_v);
+			+ (x * self.pixel_delta_u)
+			+ (y * self.pixel_delta_v);
 
 		let ray_origin = if self.defocus_angle <= 0.0 {self.center} else {self.defocus_disk_sample()};
 		let ray_direction = pixel_sample - ray_origin;
-		return Ray::from_values(&ray_origin, &ray_direction)
-
+		Ray::from_values(&ray_origin, &ray_direction)
 	}
 
 	fn sample_square(&self) -> Vec3 {
@@ -164,30 +156,74 @@ impl Camera {
 		return self.center + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v);
 	}
 
-	fn ray_color(r: &Ray, depth: i32, world: &dyn Hittable) -> Color {
-		if depth <= 0 {
-			return Color::new()
+	// Area of the image plane at unit distance in front of the camera
+	fn film_area(&self) -> f64 {
+		let width = self.pixel_delta_u.length() * self.image_width as f64/self.focus_dist;
+		let height = self.pixel_delta_v.length() * self.image_height as f64/self.focus_dist;
+		width * height
+	}
+
+	// A pinhole is a single point, it is given a unit area so the importance below still works out
+	fn lens_area(&self) -> f64 {
+		let radius = self.defocus_disk_u.length();
+		if self.defocus_angle <= 0.0 || radius <= 0.0 {
+			return 1.0
 		}
+		PI * radius * radius
+	}
 
-		let mut rec = HitRecord::new();
+	/// Solid angle density with which `get_ray` generates a ray leaving the lens along `direction`
+	pub fn pdf_direction(&self, direction: &Vec3) -> f64 {
+		let cos_theta = Vec3::dot(&Vec3::unit_vector(direction), &-self.w);
+		if cos_theta <= 0.0 {
+			return 0.0
+		}
+		1.0/(self.film_area() * cos_theta.powi(3))
+	}
 
-		if world.hit(r, &Interval::from_values(0.001, f64::INFINITY), &mut rec) {
-			let mut scattered = Ray::new();
-			let mut attenuation = Color::new();
+	/// Picks a point on the lens to connect a scene point to, for tracing light back into the camera.
+	/// Returns None if the point can't be seen by any pixel.
+	pub fn sample_importance(&self, p: &Point3) -> Option<ImportanceSample> {
+		let lens_point = if self.defocus_angle <= 0.0 {self.center} else {self.defocus_disk_sample()};
+		let to_p = *p - lens_point;
+		let distance = to_p.length();
+		let cos_theta = Vec3::dot(&(to_p/distance), &-self.w);
+		if cos_theta <= 0.0 {
+			return None
+		}
 
-			if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
-				return attenuation * Self::ray_color(&scattered, depth-1, world)
-			}
-			return Color::new();
+		// Camera rays aim at the pixel grid on the focus plane, so find where this one crosses it
+		let on_focus_plane = lens_point + (self.focus_dist/Vec3::dot(&to_p, &-self.w)) * to_p;
+		let offset = on_focus_plane - self.pixel00_loc;
+		let x = Vec3::dot(&offset, &self.pixel_delta_u)/self.pixel_delta_u.length_squared();
+		let y = Vec3::dot(&offset, &self.pixel_delta_v)/self.pixel_delta_v.length_squared();
+		let (i, j) = ((x + 0.5).floor() as i32, (y + 0.5).floor() as i32);
+		if i < 0 || i >= self.image_width || j < 0 || j >= self.image_height {
+			return None
 		}
 
-		// Scale ray direction to unit vector;
-		let unit_direction = Vec3::unit_vector(&r.direction()); // now -1.0 <= y <= 1.0
-		let a = 0.5 * (unit_direction.y() + 1.0); // a is the equivalent of y in the interval 0.0, 1.0, i,e 0.0 <= a <= 1.0
-		((1.0-a)*Color::from_xyz(1.0, 1.0, 1.0)) + (a*Color::from_xyz(0.5, 0.7, 1.0))
+		let lens_area = self.lens_area();
+		Some(ImportanceSample {
+			lens_point,
+			i,
+			j,
+			importance: 1.0/(self.film_area() * lens_area * cos_theta.powi(4)),
+			pdf: distance * distance/(cos_theta * lens_area),
+		})
 	}
 }
 
+/// A connection from a scene point back to the camera lens
+pub struct ImportanceSample {
+	pub lens_point: Point3,
+	// The pixel the connection lands on
+	pub i: i32,
+	pub j: i32,
+	pub importance: f64,
+	// Solid angle density, as seen from the scene point, of having picked `lens_point`
+	pub pdf: f64,
+}
+
 fn degrees_to_radians(deg: i32) -> f64 {
 	deg as f64 * PI/180.0
 }
\ No newline at end of file
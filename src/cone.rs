@@ -0,0 +1,153 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{disk::Disk, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, onb::Onb, ray::Ray, vec3::{Point3, Vec3}};
+
+// Below this the quadratic term is dropped and the side is solved as a line
+const E: f64 = 1e-12;
+
+/// A cone standing on `base` and pointing along `axis`, e.g a spike, a funnel or a lamp shade,
+/// optionally closed with flat caps. Truncated it narrows (or widens) to a second radius at the top instead of a point.
+/// Texture coordinates wrap u once around the axis and run v from 0 at the base to 1 at the top, caps have the polar
+/// coordinates of `Disk`.
+pub struct Cone {
+	base: Point3,
+	base_radius: f64,
+	top_radius: f64,
+	height: f64,
+	mat: Arc<dyn Material>,
+	// Unit axis and two unit axes across it, with tangent × bitangent = w
+	w: Vec3,
+	tangent: Vec3,
+	bitangent: Vec3,
+	caps: Vec<Disk>,
+}
+
+impl Cone {
+	pub fn new(base: &Point3, axis: &Vec3, radius: f64, capped: bool, mat: Arc<dyn Material>) -> Self {
+		Self::truncated(base, axis, radius, 0.0, capped, mat)
+	}
+
+	/// A cone cut off at `base + axis`, where it's `top_radius` wide
+	pub fn truncated(base: &Point3, axis: &Vec3, base_radius: f64, top_radius: f64, capped: bool, mat: Arc<dyn Material>) -> Self {
+		let (base_radius, top_radius) = (f64::max(0.0, base_radius), f64::max(0.0, top_radius));
+		let w = Vec3::unit_vector(axis);
+		let tangent = Onb::new(&w).u();
+
+		// A cap where the cone ends in a point would have nothing to close
+		let mut caps = Vec::new();
+		if capped && base_radius > 0.0 {
+			caps.push(Disk::new(base, &-w, base_radius, mat.clone()));
+		}
+		if capped && top_radius > 0.0 {
+			caps.push(Disk::new(&(*base + *axis), &w, top_radius, mat.clone()));
+		}
+
+		Self {
+			base: *base,
+			base_radius,
+			top_radius,
+			height: axis.length(),
+			mat,
+			w,
+			tangent,
+			bitangent: Vec3::cross(&w, &tangent),
+			caps,
+		}
+	}
+
+	/// How much the radius grows for each unit up the axis
+	fn slope(&self) -> f64 {
+		(self.top_radius - self.base_radius)/self.height
+	}
+
+	/// Fills in `rec` for a hit on the side at `t`, where the point is at (x, y, z) in the cone's frame
+	fn set_side(&self, r: &Ray, t: f64, x: f64, y: f64, z: f64, rec: &mut HitRecord) {
+		rec.t = t;
+		rec.p = r.at(t);
+		let phi = f64::atan2(y, x).rem_euclid(2.0 * PI);
+		(rec.u, rec.v) = (phi/(2.0 * PI), z/self.height);
+
+		let k = self.slope();
+		let radius = self.base_radius + k * z;
+		let radial = if radius > 1e-9 {(x * self.tangent + y * self.bitangent)/radius} else {self.tangent};
+		rec.tangent = 2.0 * PI * (-y * self.tangent + x * self.bitangent);
+		rec.bitangent = self.height * self.w + (self.top_radius - self.base_radius) * radial;
+
+		// The gradient of x² + y² - (r0 + kz)², with the tip's normal pointing along the axis
+		let outward_normal = if radius > 1e-9 {
+			Vec3::unit_vector(&(radial - k * self.w))
+		} else if k < 0.0 {
+			self.w
+		} else {
+			-self.w
+		};
+		rec.set_face_normal(r, &outward_normal);
+		rec.mat = self.mat.clone();
+	}
+}
+
+impl Hittable for Cone {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		if self.height <= 0.0 {
+			return false
+		}
+
+		// Work in the cone's frame, where the axis is z and the radius at height z is r0 + kz
+		let oc = r.origin() - self.base;
+		let d = r.direction();
+		let (ox, oy, oz) = (Vec3::dot(&oc, &self.tangent), Vec3::dot(&oc, &self.bitangent), Vec3::dot(&oc, &self.w));
+		let (dx, dy, dz) = (Vec3::dot(&d, &self.tangent), Vec3::dot(&d, &self.bitangent), Vec3::dot(&d, &self.w));
+		let k = self.slope();
+		let radius = self.base_radius + k * oz;
+
+		let a = dx * dx + dy * dy - k * k * dz * dz;
+		let h = ox * dx + oy * dy - k * dz * radius;
+		let c = ox * ox + oy * oy - radius * radius;
+
+		// Rays parallel to the slope only cross the side once
+		let roots = if a.abs() < E * d.length_squared() {
+			if h == 0.0 {
+				vec![]
+			} else {
+				vec![-c/(2.0 * h)]
+			}
+		} else {
+			let discriminant = h * h - a * c;
+			if discriminant < 0.0 {
+				vec![]
+			} else {
+				let sqrtd = discriminant.sqrt();
+				let (t0, t1) = ((-h - sqrtd)/a, (-h + sqrtd)/a);
+				vec![f64::min(t0, t1), f64::max(t0, t1)]
+			}
+		};
+
+		let mut hit_anything = false;
+		let mut closest = ray_t.max;
+
+		for root in roots {
+			let z = oz + root * dz;
+			if !ray_t.surrounds(root) || !(0.0..=self.height).contains(&z) {
+				continue
+			}
+
+			self.set_side(r, root, ox + root * dx, oy + root * dy, z, rec);
+			if !rec.is_cut_out(r) {
+				hit_anything = true;
+				closest = root;
+				break
+			}
+		}
+
+		let mut cap_rec = HitRecord::new();
+		for cap in &self.caps {
+			if cap.hit(r, &Interval::from_values(ray_t.min, closest), &mut cap_rec) {
+				hit_anything = true;
+				closest = cap_rec.t;
+				*rec = cap_rec.clone();
+			}
+		}
+
+		hit_anything
+	}
+}
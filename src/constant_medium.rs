@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use crate::{color::Color, hittable::{HitRecord, Hittable}, interval::Interval, material::{Isotropic, Material}, rand::random_f64, ray::Ray, vec3::Vec3};
+
+/// A volume of constant density filling the inside of a closed boundary, like fog or smoke.
+/// Rays travelling through it scatter at a random distance, more likely the denser it is.
+pub struct ConstantMedium {
+	boundary: Arc<dyn Hittable>,
+	neg_inv_density: f64,
+	phase_function: Arc<dyn Material>
+}
+
+impl ConstantMedium {
+	pub fn new(boundary: Arc<dyn Hittable>, density: f64, albedo: Color) -> Self {
+		Self::from_phase_function(boundary, density, Arc::new(Isotropic::new(albedo)))
+	}
+
+	pub fn from_phase_function(boundary: Arc<dyn Hittable>, density: f64, phase_function: Arc<dyn Material>) -> Self {
+		Self { boundary, neg_inv_density: -1.0/f64::max(0.0, density), phase_function }
+	}
+
+	/// The stretches of `ray_t` inside the boundary, in order. The boundary doesn't have to be convex,
+	/// a ray can go in and out of e.g a torus or a `Csg` several times.
+	fn segments(&self, r: &Ray, ray_t: &Interval) -> Vec<(f64, f64)> {
+		let mut segments = Vec::new();
+		// Start from behind the ray origin, so rays starting inside see where they entered
+		let mut from = f64::NEG_INFINITY;
+		let r = r.ignoring_alpha();
+		loop {
+			let mut rec1 = HitRecord::new();
+			let mut rec2 = HitRecord::new();
+			if !self.boundary.hit(&r, &Interval::from_values(from, f64::INFINITY), &mut rec1) {
+				break
+			}
+			if !self.boundary.hit(&r, &Interval::from_values(rec1.t + 0.0001, f64::INFINITY), &mut rec2) {
+				break
+			}
+
+			// Only the part of the volume inside the ray interval counts
+			let (t0, t1) = (f64::max(rec1.t, ray_t.min), f64::min(rec2.t, ray_t.max));
+			if t0 < t1 {
+				segments.push((t0, t1));
+			}
+			if rec2.t >= ray_t.max {
+				break
+			}
+			from = rec2.t + 0.0001;
+		}
+		segments
+	}
+}
+
+impl Hittable for ConstantMedium {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		// Free flight doesn't remember how far it went, so the distance just carries over from one segment to the next
+		let ray_length = r.direction().length();
+		let mut hit_distance = self.neg_inv_density * random_f64().ln();
+
+		for (t0, t1) in self.segments(r, ray_t) {
+			let t0 = f64::max(t0, 0.0);
+			if t0 >= t1 {
+				continue
+			}
+
+			let distance_inside_boundary = (t1 - t0) * ray_length;
+			if hit_distance > distance_inside_boundary {
+				hit_distance -= distance_inside_boundary;
+				continue
+			}
+
+			rec.t = t0 + hit_distance/ray_length;
+			rec.p = r.at(rec.t);
+
+			// A point inside a volume has no real normal or face, these are arbitrary
+			rec.normal = Vec3::from_xyz(1.0, 0.0, 0.0);
+			(rec.u, rec.v) = (0.0, 0.0);
+			(rec.tangent, rec.bitangent) = (Vec3::new(), Vec3::new());
+			rec.front_face = true;
+			rec.mat = self.phase_function.clone();
+
+			return true
+		}
+
+		false
+	}
+
+	fn hit_solid(&self, _r: &Ray, _ray_t: &Interval, _rec: &mut HitRecord) -> bool {
+		false
+	}
+
+	fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+		// Beer-Lambert, the density is the same everywhere so no need to estimate anything
+		let inside: f64 = self.segments(r, ray_t).iter().map(|(t0, t1)| t1 - t0).sum();
+		let distance = inside * r.direction().length();
+		let a = f64::exp(distance/self.neg_inv_density);
+		Color::from_xyz(a, a, a)
+	}
+}
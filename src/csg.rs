@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use crate::{hittable::{HitRecord, Hittable, Span}, interval::Interval, ray::Ray};
+
+/// How `Csg` combines its two shapes
+#[derive(Clone, Copy)]
+pub enum CsgOperation {
+	// Inside either shape
+	Union,
+	// Inside both shapes
+	Intersection,
+	// Inside the first shape but not the second
+	Difference,
+}
+
+/// A boolean combination of two solids, e.g a cube with a sphere carved out of it or a lens from two spheres.
+/// The shapes have to report their `spans`, which `Sphere`, `Cube` and `Csg` itself do, so combinations nest,
+/// and normal or bump maps pass them through. Anything else, e.g a `HittableList`, doesn't show up.
+///
+/// Each part of the surface keeps the material of the shape it came from,
+/// so the hollow a difference carves out shows the material of the shape carving it.
+pub struct Csg {
+	operation: CsgOperation,
+	a: Arc<dyn Hittable>,
+	b: Arc<dyn Hittable>,
+}
+
+impl Csg {
+	pub fn new(operation: CsgOperation, a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Self {
+		Self { operation, a, b }
+	}
+
+	pub fn union(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Self {
+		Self::new(CsgOperation::Union, a, b)
+	}
+
+	pub fn intersection(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Self {
+		Self::new(CsgOperation::Intersection, a, b)
+	}
+
+	/// `a` with `b` carved out of it
+	pub fn difference(a: Arc<dyn Hittable>, b: Arc<dyn Hittable>) -> Self {
+		Self::new(CsgOperation::Difference, a, b)
+	}
+
+	fn contains(&self, in_a: bool, in_b: bool) -> bool {
+		match self.operation {
+			CsgOperation::Union => in_a || in_b,
+			CsgOperation::Intersection => in_a && in_b,
+			CsgOperation::Difference => in_a && !in_b,
+		}
+	}
+}
+
+impl Hittable for Csg {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		for span in self.spans(r) {
+			for candidate in [span.enter, span.exit] {
+				if !ray_t.surrounds(candidate.t) {
+					continue
+				}
+
+				*rec = candidate;
+				if !rec.is_cut_out(r) {
+					return true
+				}
+			}
+		}
+
+		false
+	}
+
+	fn spans(&self, r: &Ray) -> Vec<Span> {
+		// Every boundary of either shape in order along the ray, as (hit, whether it's b's, whether the ray goes in)
+		let mut boundaries = Vec::new();
+		for (shape, is_b) in [(&self.a, false), (&self.b, true)] {
+			for span in shape.spans(r) {
+				boundaries.push((span.enter, is_b, true));
+				boundaries.push((span.exit, is_b, false));
+			}
+		}
+		boundaries.sort_by(|x, y| x.0.t.total_cmp(&y.0.t));
+
+		// Walk along the ray keeping track of which shapes it's in, the result's boundaries are where that changes it
+		let (mut in_a, mut in_b) = (false, false);
+		let mut enter: Option<HitRecord> = None;
+		let mut spans = Vec::new();
+		for (mut boundary, is_b, entering) in boundaries {
+			let was_inside = self.contains(in_a, in_b);
+			if is_b {
+				in_b = entering;
+			} else {
+				in_a = entering;
+			}
+			let inside = self.contains(in_a, in_b);
+			if inside == was_inside {
+				continue
+			}
+
+			// Going into b means leaving a difference and the other way around, so its surface faces the other way.
+			// The normal still faces the ray, only which side of the surface it's on changes.
+			if is_b && matches!(self.operation, CsgOperation::Difference) {
+				boundary.front_face = !boundary.front_face;
+			}
+
+			if inside {
+				// A span starting right where the last one ended, e.g where a surface is shared, carries on from it
+				enter = match spans.last() {
+					Some(Span { exit, .. }) if exit.t == boundary.t => spans.pop().map(|span| span.enter),
+					_ => Some(boundary),
+				};
+			} else if let Some(enter) = enter.take() {
+				spans.push(Span { enter, exit: boundary });
+			}
+		}
+		spans
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{cube::Cube, material::blank_material, sphere::Sphere, vec3::{Point3, Vec3}};
+
+	#[test]
+	fn difference_spans() {
+		// A cube from -1 to 1 with a ball of radius 0.5 carved out of its middle, seen along x
+		let cube = Arc::new(Cube::new(&Point3::new(), 2.0, blank_material()));
+		let ball = Arc::new(Sphere::new(&Point3::new(), 0.5, blank_material()));
+		let r = Ray::from_values(&Point3::from_xyz(-5.0, 0.0, 0.0), &Vec3::from_xyz(1.0, 0.0, 0.0));
+		let spans = Csg::difference(cube, ball).spans(&r);
+
+		assert_eq!(spans.len(), 2);
+		for (span, (enter, exit)) in spans.iter().zip([(4.0, 4.5), (5.5, 6.0)]) {
+			assert!((span.enter.t - enter).abs() < 1e-9 && (span.exit.t - exit).abs() < 1e-9, "span from {} to {}", span.enter.t, span.exit.t);
+			// Going into the solid part is a front face and leaving it a back face, on the ball's surface as well
+			assert!(span.enter.front_face && !span.exit.front_face);
+			assert!(span.enter.normal.x() < 0.0 && span.exit.normal.x() < 0.0);
+		}
+	}
+}
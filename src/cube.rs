@@ -1,21 +1,29 @@
-use crate::{hittable::Hittable, interval::Interval, vec3::{Point3, Vec3}};
+use std::sync::Arc;
+
+use crate::{hittable::{HitRecord, Hittable, Span}, interval::Interval, material::Material, ray::Ray, vec3::{Point3, Vec3}};
 
 pub struct Cube {
 	pub center: Point3,
-	pub side_length: f64
+	pub side_length: f64,
+	mat: Arc<dyn Material>
 }
 
 // Parallel ray epsilon
 const E: f64 = 0.000001;
 
 impl Cube {
-	pub fn new(center: &Point3, side_length: f64) -> Self {
-		Self { center: center.clone(), side_length: f64::max(0.0, side_length) }
+	pub fn new(center: &Point3, side_length: f64, mat: Arc<dyn Material>) -> Self {
+		Self { center: center.clone(), side_length: f64::max(0.0, side_length), mat }
 	}
-}
 
-impl Hittable for Cube {
-	fn hit(&self, r: &crate::ray::Ray, ray_t: &Interval, rec: &mut crate::hittable::HitRecord) -> bool {
+	/// Where the line through `r` enters and leaves the cube, each with the axis of the face (X = 0, Y = 1, Z = 2, 3 for none)
+	/// and its side (1 for the max bound, -1 for the min bound). None if it misses.
+	fn crossings(&self, r: &Ray) -> Option<[(f64, usize, i32); 2]> {
+		// A cube without size has no faces to hit
+		if self.side_length <= 0.0 {
+			return None
+		}
+
 		// A cube in 3d geometric space at origin (0, 0, 0) with side length 2a
     // i.e, sides extend from -a to +a is defined by the inequalities
     // -a <= x <= a, -a <= y <= a, -a <= z <= a
@@ -47,7 +55,8 @@ impl Hittable for Cube {
     let q = r.origin(); // Q
     let v1 = c-a;
     let v2 = c+a;
-    let d = Vec3::unit_vector(&r.direction());
+    // Not normalized, so t is in the same units as the ray and r.at(t) lands on the cube
+    let d = r.direction();
 
 		
     // if v1 <= t*d <= v2 for any given t
@@ -57,8 +66,10 @@ impl Hittable for Cube {
 
 		// Now we iterate through all axes, maximizing t_max and minimizing t_min, which are the points of intersection between ray and cube.
 
-		let mut hit_axis = 3; // X = 0, Y = 1, Z = 2;
-		let mut hit_sign = 0;  // 1 for max_bound hit, -1 for min_bound hit
+		// The faces the ray enters and leaves through, as axis (X = 0, Y = 1, Z = 2, 3 for none yet)
+		// and sign (1 for max_bound hit, -1 for min_bound hit)
+		let (mut enter_axis, mut enter_sign) = (3, 0);
+		let (mut exit_axis, mut exit_sign) = (3, 0);
 
     for (i, (((v1, v2), q), d)) in v1.iter().zip(v2.iter()).zip(q.iter()).zip(d.iter()).enumerate() {
 			let mut t1 = (v1 - q)/d;
@@ -66,46 +77,38 @@ impl Hittable for Cube {
 			
 			if d.abs() < E {
 				if q < v1 || q > v2 {
-					return false
+					return None
 				}
 			} else {
 				let mut swapped = false;
 				// check if max, min swapped
 				if t1 > t2 {(t1, t2) = (t2, t1); swapped = true}
 				
-				// maximize t_min
-				t_min = t_min.max(t1);
-				// check if the face hit (i.e, the value t_min) came from the current axis
-				if t1 == t_min {hit_axis = i}
-				// adjust the hit sign to correspond with the hit point
-				hit_sign = if swapped {1} else {-1};
-				// minimize t_max;
-				t_max = t_max.min(t2);
+				// maximize t_min, remembering which face it came from
+				if t1 > t_min {
+					t_min = t1;
+					enter_axis = i;
+					enter_sign = if swapped {1} else {-1};
+				}
+				// minimize t_max, the ray leaves through the opposite bound
+				if t2 < t_max {
+					t_max = t2;
+					exit_axis = i;
+					exit_sign = if swapped {-1} else {1};
+				}
 				
-				if t_min > t_max {return false}
+				if t_min > t_max {return None}
 			}
 			
 			// eprintln!("{:?}", (t_min, t_max))
     }
 
-		if !ray_t.surrounds(t_min) {
-			t_min = t_max;
-
-			if !ray_t.surrounds(t_min) {
-				return false
-			}
-		}
-
-		// if t_min < 0.0 {
-		// 	// ray starts inside the cube, or at least behind the camera
-		// 	if t_max < 0.0 {
-		// 		// cube is entirely behind the camera
-		// 		return false
-		// 	}
-		// }
+		Some([(t_min, enter_axis, enter_sign), (t_max, exit_axis, exit_sign)])
+	}
 
-		// t_min is lower than tmax and is the first point of intersection of the ray
-		rec.t = t_min;
+	/// Fills in `rec` for a hit at `t` on the face along `hit_axis` on side `hit_sign`
+	fn set_hit(&self, r: &Ray, t: f64, hit_axis: usize, hit_sign: i32, rec: &mut HitRecord) {
+		rec.t = t;
 		rec.p = r.at(rec.t);
 		let mut outward_normal = Vec3::new();
 		if hit_axis != 3 {
@@ -114,12 +117,62 @@ impl Hittable for Cube {
 			1 => Vec3::from_xyz(0.0 , hit_sign as f64, 0.0),
 			_ => Vec3::from_xyz(0.0 , 0.0, hit_sign as f64),
 			};
+
+			// Texture coordinates run over the face along the other two axes,
+			// with u flipped where needed so images aren't mirrored when seen from outside
+			let (ua, va) = match hit_axis {
+				0 => (2, 1),
+				1 => (0, 2),
+				_ => (0, 1),
+			};
+			let flip_u = if hit_axis == 0 {hit_sign > 0} else {hit_sign < 0};
+			let local = (rec.p - self.center)/self.side_length + Vec3::from_xyz(0.5, 0.5, 0.5);
+			rec.u = if flip_u {1.0 - local[ua]} else {local[ua]};
+			rec.v = local[va];
+
+			let axis = |i: usize, length: f64| match i {
+				0 => Vec3::from_xyz(length, 0.0, 0.0),
+				1 => Vec3::from_xyz(0.0, length, 0.0),
+				_ => Vec3::from_xyz(0.0, 0.0, length),
+			};
+			rec.tangent = axis(ua, if flip_u {-self.side_length} else {self.side_length});
+			rec.bitangent = axis(va, self.side_length);
 		}
 		rec.set_face_normal(r, &outward_normal);
-		
-    return true;
+		rec.mat = self.mat.clone();
+	}
+}
+
+impl Hittable for Cube {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		let Some(crossings) = self.crossings(r) else {
+			return false
+		};
+
+		// The ray hits where it enters the cube, or where it leaves if it started inside or the entry is cut out
+		for (t, hit_axis, hit_sign) in crossings {
+			if !ray_t.surrounds(t) {
+				continue
+			}
+
+			self.set_hit(r, t, hit_axis, hit_sign, rec);
+			if !rec.is_cut_out(r) {
+				return true
+			}
+		}
+
+		false
+	}
 
+	fn spans(&self, r: &Ray) -> Vec<Span> {
+		let Some([(t_enter, enter_axis, enter_sign), (t_exit, exit_axis, exit_sign)]) = self.crossings(r) else {
+			return Vec::new()
+		};
 
+		let (mut enter, mut exit) = (HitRecord::new(), HitRecord::new());
+		self.set_hit(r, t_enter, enter_axis, enter_sign, &mut enter);
+		self.set_hit(r, t_exit, exit_axis, exit_sign, &mut exit);
+		vec![Span { enter, exit }]
 	}
 
 	// My attempt at checking for ray collision with cube
@@ -0,0 +1,114 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{disk::Disk, hittable::{HitRecord, Hittable}, interval::Interval, material::Material, onb::Onb, ray::Ray, vec3::{Point3, Vec3}};
+
+/// A round tube from `base` to `base + axis`, e.g a pipe, a column or a can, optionally closed with flat caps.
+/// Texture coordinates wrap u once around the axis and run v from 0 at the base to 1 at the top, caps have the polar
+/// coordinates of `Disk`. Without caps the inside can be seen, and it shows as the back face.
+/// `tube` makes a hollow one with a wall between two radii, with rings for caps.
+pub struct Cylinder {
+	base: Point3,
+	axis: Vec3,
+	radius: f64,
+	// Radius of the hole through the middle, 0 for a solid cylinder
+	inner_radius: f64,
+	mat: Arc<dyn Material>,
+	// Unit axes across the axis, with tangent × bitangent along the axis
+	tangent: Vec3,
+	bitangent: Vec3,
+	caps: Vec<Disk>,
+}
+
+impl Cylinder {
+	pub fn new(base: &Point3, axis: &Vec3, radius: f64, capped: bool, mat: Arc<dyn Material>) -> Self {
+		Self::tube(base, axis, 0.0, radius, capped, mat)
+	}
+
+	/// A hollow cylinder, e.g a pipe with a wall, whose hole between `inner_radius` and the axis goes all the way through
+	pub fn tube(base: &Point3, axis: &Vec3, inner_radius: f64, radius: f64, capped: bool, mat: Arc<dyn Material>) -> Self {
+		let radius = f64::max(0.0, radius);
+		let inner_radius = inner_radius.clamp(0.0, radius);
+		let w = Vec3::unit_vector(axis);
+		let tangent = Onb::new(&w).u();
+		let caps = if capped {
+			vec![
+				Disk::annulus(base, &-w, inner_radius, radius, mat.clone()),
+				Disk::annulus(&(*base + *axis), &w, inner_radius, radius, mat.clone()),
+			]
+		} else {
+			Vec::new()
+		};
+		Self { base: *base, axis: *axis, radius, inner_radius, mat, tangent, bitangent: Vec3::cross(&w, &tangent), caps }
+	}
+
+	/// Fills in `rec` for a hit on the wall of `radius` at `t`, where the point is (x, y) across the axis and `z` of the way up.
+	/// The inner wall's radius is negative, its outside faces the axis.
+	fn set_side(&self, r: &Ray, t: f64, (x, y, z): (f64, f64, f64), radius: f64, rec: &mut HitRecord) {
+		rec.t = t;
+		rec.p = r.at(t);
+		let phi = f64::atan2(y, x).rem_euclid(2.0 * PI);
+		(rec.u, rec.v) = (phi/(2.0 * PI), z);
+		rec.tangent = 2.0 * PI * (-y * self.tangent + x * self.bitangent);
+		rec.bitangent = self.axis;
+		let outward_normal = (x * self.tangent + y * self.bitangent)/radius;
+		rec.set_face_normal(r, &outward_normal);
+		rec.mat = self.mat.clone();
+	}
+}
+
+impl Hittable for Cylinder {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		// Work in the cylinder's frame, where the axis is z and z is measured in units of its length
+		let height2 = self.axis.length_squared();
+		if height2 <= 0.0 || self.radius <= 0.0 {
+			return false
+		}
+		let oc = r.origin() - self.base;
+		let d = r.direction();
+		let (ox, oy, oz) = (Vec3::dot(&oc, &self.tangent), Vec3::dot(&oc, &self.bitangent), Vec3::dot(&oc, &self.axis)/height2);
+		let (dx, dy, dz) = (Vec3::dot(&d, &self.tangent), Vec3::dot(&d, &self.bitangent), Vec3::dot(&d, &self.axis)/height2);
+
+		let mut hit_anything = false;
+		let mut closest = ray_t.max;
+
+		let a = dx * dx + dy * dy;
+		let h = ox * dx + oy * dy;
+		let walls: &[f64] = if self.inner_radius > 0.0 {&[self.radius, -self.inner_radius]} else {&[self.radius]};
+		let mut side_rec = HitRecord::new();
+		for radius in walls {
+			let c = ox * ox + oy * oy - radius * radius;
+			let discriminant = h * h - a * c;
+			if a <= 0.0 || discriminant < 0.0 {
+				continue
+			}
+
+			let sqrtd = discriminant.sqrt();
+			for root in [(-h - sqrtd)/a, (-h + sqrtd)/a] {
+				let z = oz + root * dz;
+				if root <= ray_t.min || root >= closest || !(0.0..=1.0).contains(&z) {
+					continue
+				}
+
+				// The other wall may still be closer, so only keep hits that aren't cut out
+				self.set_side(r, root, (ox + root * dx, oy + root * dy, z), *radius, &mut side_rec);
+				if !side_rec.is_cut_out(r) {
+					hit_anything = true;
+					closest = root;
+					*rec = side_rec.clone();
+					break
+				}
+			}
+		}
+
+		let mut cap_rec = HitRecord::new();
+		for cap in &self.caps {
+			if cap.hit(r, &Interval::from_values(ray_t.min, closest), &mut cap_rec) {
+				hit_anything = true;
+				closest = cap_rec.t;
+				*rec = cap_rec.clone();
+			}
+		}
+
+		hit_anything
+	}
+}
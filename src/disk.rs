@@ -0,0 +1,140 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{hittable::{HitRecord, Hittable}, interval::Interval, material::Material, onb::Onb, rand::random_f64, ray::Ray, vec3::{Point3, Vec3}};
+
+// Parallel ray epsilon
+const E: f64 = 1e-8;
+
+/// A flat round disk facing along `normal`, e.g a round table top, a lid or a round light.
+/// With a hole in the middle it's an annulus, e.g a washer or the end of a pipe.
+/// Texture coordinates are polar: u goes once around the center and v from 0 at the center (or the hole) to 1 at the rim.
+pub struct Disk {
+	center: Point3,
+	radius: f64,
+	inner_radius: f64,
+	mat: Arc<dyn Material>,
+	// Unit normal and two unit axes in the disk's plane, with tangent × bitangent = normal
+	normal: Vec3,
+	tangent: Vec3,
+	bitangent: Vec3,
+}
+
+impl Disk {
+	pub fn new(center: &Point3, normal: &Vec3, radius: f64, mat: Arc<dyn Material>) -> Self {
+		Self::annulus(center, normal, 0.0, radius, mat)
+	}
+
+	/// A ring between `inner_radius` and `radius`
+	pub fn annulus(center: &Point3, normal: &Vec3, inner_radius: f64, radius: f64, mat: Arc<dyn Material>) -> Self {
+		let normal = Vec3::unit_vector(normal);
+		let tangent = Onb::new(&normal).u();
+		let radius = f64::max(0.0, radius);
+		Self {
+			center: *center,
+			radius,
+			inner_radius: inner_radius.clamp(0.0, radius),
+			mat,
+			normal,
+			tangent,
+			bitangent: Vec3::cross(&normal, &tangent),
+		}
+	}
+
+	fn area(&self) -> f64 {
+		PI * (self.radius * self.radius - self.inner_radius * self.inner_radius)
+	}
+
+	fn contains(&self, distance_squared: f64) -> bool {
+		distance_squared <= self.radius * self.radius && distance_squared >= self.inner_radius * self.inner_radius
+	}
+
+	/// Fills in the texture coordinates and tangents for a point at (x, y) in the disk's plane
+	fn set_surface(&self, x: f64, y: f64, rec: &mut HitRecord) {
+		let r = f64::sqrt(x * x + y * y);
+		let phi = f64::atan2(y, x).rem_euclid(2.0 * PI);
+		rec.u = phi/(2.0 * PI);
+		let width = self.radius - self.inner_radius;
+		rec.v = if width > 0.0 {(r - self.inner_radius)/width} else {0.0};
+
+		if r < 1e-9 {
+			// u is undefined at the center, any frame in the plane will do
+			(rec.tangent, rec.bitangent) = (2.0 * PI * self.radius * self.bitangent, width * self.tangent);
+			return
+		}
+		rec.tangent = 2.0 * PI * (-y * self.tangent + x * self.bitangent);
+		rec.bitangent = width/r * (x * self.tangent + y * self.bitangent);
+	}
+}
+
+impl Hittable for Disk {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		let denom = Vec3::dot(&self.normal, &r.direction());
+		if denom.abs() < E {
+			return false
+		}
+
+		let t = Vec3::dot(&self.normal, &(self.center - r.origin()))/denom;
+		if !ray_t.surrounds(t) {
+			return false
+		}
+
+		let p = r.at(t);
+		let local = p - self.center;
+		let (x, y) = (Vec3::dot(&local, &self.tangent), Vec3::dot(&local, &self.bitangent));
+		if !self.contains(x * x + y * y) {
+			return false
+		}
+
+		rec.t = t;
+		rec.p = p;
+		self.set_surface(x, y, rec);
+		rec.set_face_normal(r, &self.normal);
+		rec.mat = self.mat.clone();
+
+		!rec.is_cut_out(r)
+	}
+
+	fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+		let mut rec = HitRecord::new();
+		if !self.hit(&Ray::from_values(origin, direction).ignoring_alpha(), &Interval::from_values(0.001, f64::INFINITY), &mut rec) {
+			return 0.0
+		}
+
+		let distance_squared = rec.t * rec.t * direction.length_squared();
+		let cosine = (Vec3::dot(direction, &self.normal)/direction.length()).abs();
+		distance_squared/(cosine * self.area())
+	}
+
+	fn random(&self, origin: &Point3) -> Vec3 {
+		let mut rec = HitRecord::new();
+		self.sample_surface(&mut rec);
+		rec.p - *origin
+	}
+
+	fn sample_surface(&self, rec: &mut HitRecord) -> f64 {
+		let inner2 = self.inner_radius * self.inner_radius;
+		let r = f64::sqrt(inner2 + random_f64() * (self.radius * self.radius - inner2));
+		let phi = 2.0 * PI * random_f64();
+		let (x, y) = (r * phi.cos(), r * phi.sin());
+
+		rec.p = self.center + (x * self.tangent) + (y * self.bitangent);
+		rec.normal = self.normal;
+		self.set_surface(x, y, rec);
+		rec.front_face = true;
+		rec.mat = self.mat.clone();
+
+		1.0/self.area()
+	}
+
+	fn surface_pdf(&self, point: &Point3) -> f64 {
+		let local = *point - self.center;
+		if Vec3::dot(&local, &self.normal).abs() > 1e-6 * f64::max(1.0, self.radius) {
+			return 0.0
+		}
+		if !self.contains(local.length_squared()) {
+			return 0.0
+		}
+
+		1.0/self.area()
+	}
+}
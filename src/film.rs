@@ -0,0 +1,53 @@
+use std::{io::{self, Write}, sync::Mutex};
+
+use crate::color::{write_color, Color};
+
+/// The float framebuffer an image is accumulated into before being written out
+pub struct Film {
+	pub width: i32,
+	pub height: i32,
+	pixels: Mutex<Vec<Color>>,
+	// Contributions that land on arbitrary pixels, e.g from light tracing.
+	// These are scaled separately when the image is written out.
+	splats: Mutex<Vec<Color>>,
+}
+
+impl Film {
+	pub fn new(width: i32, height: i32) -> Self {
+		let size = (width * height) as usize;
+		Self {
+			width,
+			height,
+			pixels: Mutex::new(vec![Color::new(); size]),
+			splats: Mutex::new(vec![Color::new(); size]),
+		}
+	}
+
+	pub fn set_pixel(&self, i: i32, j: i32, color: Color) {
+		self.pixels.lock().unwrap()[(j * self.width + i) as usize] = color;
+	}
+
+	pub fn add_splat(&self, i: i32, j: i32, color: Color) {
+		if i < 0 || i >= self.width || j < 0 || j >= self.height {
+			return
+		}
+		self.splats.lock().unwrap()[(j * self.width + i) as usize] += color;
+	}
+
+	/// Writes the image as a plain text ppm to stdout
+	pub fn write_ppm(&self, splat_scale: f64) {
+		let stdout = io::stdout();
+		let lock = stdout.lock();
+		let mut w = io::BufWriter::new(lock);
+
+		writeln!(&mut w, "P3\n{} {}\n255", self.width, self.height).unwrap();
+
+		let pixels = self.pixels.lock().unwrap();
+		let splats = self.splats.lock().unwrap();
+		for (pixel, splat) in pixels.iter().zip(splats.iter()) {
+			writeln!(&mut w, "{}", write_color(&(*pixel + splat_scale * *splat))).unwrap();
+		}
+
+		w.flush().unwrap();
+	}
+}
@@ -0,0 +1,263 @@
+use std::{fs, io, path::Path, sync::Arc};
+
+use crate::{color::Color, hittable::{HitRecord, Hittable}, interval::Interval, material::{DiffuseLight, Material}, rand::random_f64, ray::Ray, vec3::{Point3, Vec3}};
+
+fn invalid(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// A dense 3D grid of densities, looked up with trilinear filtering
+pub struct VoxelGrid {
+	nx: usize,
+	ny: usize,
+	nz: usize,
+	// x varies fastest, then y, then z
+	data: Vec<f64>,
+	max: f64,
+}
+
+impl VoxelGrid {
+	/// A grid from nx*ny*nz densities, x varying fastest, then y, then z. Negative densities are clamped to 0.
+	pub fn new(nx: usize, ny: usize, nz: usize, data: Vec<f64>) -> io::Result<Self> {
+		if data.len() != Self::voxel_count(nx, ny, nz)? {
+			return Err(invalid("voxel data doesn't match the grid size"))
+		}
+		let data: Vec<f64> = data.into_iter().map(|d| f64::max(0.0, d)).collect();
+		let max = data.iter().cloned().fold(0.0, f64::max);
+		Ok(Self { nx, ny, nz, data, max })
+	}
+
+	/// nx*ny*nz, failing if any of them is zero or it doesn't fit in a usize
+	fn voxel_count(nx: usize, ny: usize, nz: usize) -> io::Result<usize> {
+		if nx == 0 || ny == 0 || nz == 0 {
+			return Err(invalid("grid size can't be zero"))
+		}
+		nx.checked_mul(ny).and_then(|n| n.checked_mul(nz)).ok_or_else(|| invalid("grid size is too large"))
+	}
+
+	/// Fills a grid by evaluating `f` at every voxel center, in coordinates from 0 to 1 along each axis.
+	/// Signed functions like noise are fine, the negative part is left empty.
+	pub fn from_fn(nx: usize, ny: usize, nz: usize, f: impl Fn(f64, f64, f64) -> f64) -> io::Result<Self> {
+		let mut data = Vec::with_capacity(Self::voxel_count(nx, ny, nz)?);
+		for z in 0..nz {
+			for y in 0..ny {
+				for x in 0..nx {
+					data.push(f((x as f64 + 0.5)/nx as f64, (y as f64 + 0.5)/ny as f64, (z as f64 + 0.5)/nz as f64));
+				}
+			}
+		}
+		Self::new(nx, ny, nz, data)
+	}
+
+	/// Loads a grid from a "VDB-lite" file: a text header line `VOXELS <nx> <ny> <nz>`
+	/// followed by nx*ny*nz little-endian f32 densities, x varying fastest, then y, then z.
+	pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+		let bytes = fs::read(path)?;
+
+		let header_end = bytes.iter().position(|&b| b == b'\n').ok_or_else(|| invalid("missing header"))?;
+		let header = std::str::from_utf8(&bytes[..header_end]).map_err(|_| invalid("header is not text"))?;
+		let fields: Vec<&str> = header.split_whitespace().collect();
+		if fields.len() != 4 || fields[0] != "VOXELS" {
+			return Err(invalid("expected a `VOXELS <nx> <ny> <nz>` header"))
+		}
+
+		let size: Vec<usize> = fields[1..].iter().map(|f| f.parse().map_err(|_| invalid("bad grid size"))).collect::<io::Result<_>>()?;
+		let (nx, ny, nz) = (size[0], size[1], size[2]);
+
+		let byte_count = Self::voxel_count(nx, ny, nz)?.checked_mul(4).ok_or_else(|| invalid("grid size is too large"))?;
+
+		let body = &bytes[header_end + 1..];
+		if body.len() != byte_count {
+			return Err(invalid("voxel data doesn't match the grid size"))
+		}
+
+		let data = body.chunks_exact(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64).collect();
+		Self::new(nx, ny, nz, data)
+	}
+
+	pub fn max(&self) -> f64 {
+		self.max
+	}
+
+	fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+		self.data[(z * self.ny + y) * self.nx + x]
+	}
+
+	/// Trilinearly filtered density at `p`, in coordinates from 0 to 1 along each axis
+	pub fn lookup(&self, p: &Point3) -> f64 {
+		// Voxel values sit at voxel centers, clamp to the edge ones outside of them
+		let coord = |v: f64, n: usize| {
+			let x = (v * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+			let i = (x.floor() as usize).min(n.saturating_sub(2));
+			(i, (i + 1).min(n - 1), x - i as f64)
+		};
+		let (x0, x1, fx) = coord(p.x(), self.nx);
+		let (y0, y1, fy) = coord(p.y(), self.ny);
+		let (z0, z1, fz) = coord(p.z(), self.nz);
+
+		let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+		let c00 = lerp(self.voxel(x0, y0, z0), self.voxel(x1, y0, z0), fx);
+		let c10 = lerp(self.voxel(x0, y1, z0), self.voxel(x1, y1, z0), fx);
+		let c01 = lerp(self.voxel(x0, y0, z1), self.voxel(x1, y0, z1), fx);
+		let c11 = lerp(self.voxel(x0, y1, z1), self.voxel(x1, y1, z1), fx);
+
+		lerp(lerp(c00, c10, fy), lerp(c01, c11, fy), fz)
+	}
+}
+
+/// A heterogeneous volume, e.g smoke or a cloud, whose density comes from a voxel grid stretched over a box.
+/// Collisions are found with delta tracking against the grid's maximum density, and shadow rays
+/// are attenuated with ratio tracking instead of being stopped.
+///
+/// At a collision the ray is absorbed (picking up `emission`) or scattered by the phase function,
+/// in proportion to the absorption and scattering coefficients, which are per unit of grid density.
+pub struct GridMedium {
+	grid: VoxelGrid,
+	bounds_min: Point3,
+	bounds_max: Point3,
+	sigma_a: f64,
+	sigma_s: f64,
+	absorbed: Arc<dyn Material>,
+	phase_function: Arc<dyn Material>,
+}
+
+impl GridMedium {
+	pub fn new(grid: VoxelGrid, bounds_min: &Point3, bounds_max: &Point3, sigma_a: f64, sigma_s: f64, emission: Color, phase_function: Arc<dyn Material>) -> Self {
+		Self {
+			grid,
+			bounds_min: *bounds_min,
+			bounds_max: *bounds_max,
+			sigma_a: f64::max(0.0, sigma_a),
+			sigma_s: f64::max(0.0, sigma_s),
+			absorbed: Arc::new(DiffuseLight::new(emission)),
+			phase_function,
+		}
+	}
+
+	/// Density of the grid at a world space point
+	fn density(&self, p: &Point3) -> f64 {
+		let size = self.bounds_max - self.bounds_min;
+		let local = *p - self.bounds_min;
+		self.grid.lookup(&Vec3::from_xyz(local.x()/size.x(), local.y()/size.y(), local.z()/size.z()))
+	}
+
+	/// The part of `ray_t` where the ray is inside the bounding box, if any
+	fn clip(&self, r: &Ray, ray_t: &Interval) -> Option<(f64, f64)> {
+		let mut t0 = ray_t.min;
+		let mut t1 = ray_t.max;
+		for axis in 0..3 {
+			let inv_d = 1.0/r.direction()[axis];
+			let mut near = (self.bounds_min[axis] - r.origin()[axis]) * inv_d;
+			let mut far = (self.bounds_max[axis] - r.origin()[axis]) * inv_d;
+			if near > far {(near, far) = (far, near)}
+			t0 = t0.max(near);
+			t1 = t1.min(far);
+			if t0 >= t1 {
+				return None
+			}
+		}
+		Some((t0, t1))
+	}
+
+	// Extinction everywhere in the volume is at most this
+	fn sigma_majorant(&self) -> f64 {
+		self.grid.max() * (self.sigma_a + self.sigma_s)
+	}
+}
+
+impl Hittable for GridMedium {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		let sigma_maj = self.sigma_majorant();
+		let Some((t0, t1)) = self.clip(r, ray_t) else {
+			return false
+		};
+		if sigma_maj <= 0.0 {
+			return false
+		}
+
+		// Delta tracking: step through the volume as if it were uniformly as dense as the majorant,
+		// then decide at each tentative collision whether it was real
+		let ray_length = r.direction().length();
+		let mut t = t0;
+		loop {
+			t -= f64::ln(1.0 - random_f64())/(sigma_maj * ray_length);
+			if t >= t1 {
+				return false
+			}
+
+			let p = r.at(t);
+			let density = self.density(&p);
+			let xi = random_f64() * sigma_maj;
+
+			let mat = if xi < density * self.sigma_a {
+				&self.absorbed
+			} else if xi < density * (self.sigma_a + self.sigma_s) {
+				&self.phase_function
+			} else {
+				// null collision, keep going
+				continue
+			};
+
+			rec.t = t;
+			rec.p = p;
+			// A point inside a volume has no real normal or face, these are arbitrary
+			rec.normal = Vec3::from_xyz(1.0, 0.0, 0.0);
+			(rec.u, rec.v) = (0.0, 0.0);
+			(rec.tangent, rec.bitangent) = (Vec3::new(), Vec3::new());
+			rec.front_face = true;
+			rec.mat = mat.clone();
+
+			return true
+		}
+	}
+
+	fn hit_solid(&self, _r: &Ray, _ray_t: &Interval, _rec: &mut HitRecord) -> bool {
+		false
+	}
+
+	fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+		let sigma_maj = self.sigma_majorant();
+		let Some((t0, t1)) = self.clip(r, ray_t) else {
+			return Color::from_xyz(1.0, 1.0, 1.0)
+		};
+		if sigma_maj <= 0.0 {
+			return Color::from_xyz(1.0, 1.0, 1.0)
+		}
+
+		// Ratio tracking: same steps as delta tracking, but every tentative collision
+		// scales the estimate by the chance of it having been a null collision
+		let ray_length = r.direction().length();
+		let mut transmittance = 1.0;
+		let mut t = t0;
+		loop {
+			t -= f64::ln(1.0 - random_f64())/(sigma_maj * ray_length);
+			if t >= t1 {
+				break
+			}
+
+			let density = self.density(&r.at(t));
+			transmittance *= 1.0 - density * (self.sigma_a + self.sigma_s)/sigma_maj;
+		}
+
+		Color::from_xyz(transmittance, transmittance, transmittance)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn negative_densities_are_empty() {
+		let grid = VoxelGrid::from_fn(2, 1, 1, |x, _, _| if x < 0.5 {-1.0} else {2.0}).unwrap();
+		assert_eq!(grid.max(), 2.0);
+		assert_eq!(grid.lookup(&Point3::from_xyz(0.0, 0.5, 0.5)), 0.0);
+	}
+
+	#[test]
+	fn bad_sizes_are_rejected() {
+		assert!(VoxelGrid::new(0, 1, 1, Vec::new()).is_err());
+		assert!(VoxelGrid::new(2, 2, 2, vec![1.0; 7]).is_err());
+		assert!(VoxelGrid::from_fn(usize::MAX, 2, 1, |_, _, _| 1.0).is_err());
+	}
+}
@@ -1,28 +1,93 @@
 use std::{sync::Arc};
 
-use crate::{interval::Interval, material::{blank_material, Material}, ray::Ray, vec3::{Point3, Vec3}};
+use crate::{color::Color, interval::Interval, material::{blank_material, Material}, rand::random_f64, ray::Ray, vec3::{Point3, Vec3}};
 
 #[derive(Clone)]
 pub struct HitRecord {
 	pub p: Point3,
 	pub normal: Vec3,
 	pub t: f64,
+	// Surface texture coordinates
+	pub u: f64,
+	pub v: f64,
+	// How the surface point moves with u and v (∂p/∂u and ∂p/∂v), not normalized.
+	// Together with the outward normal they give the tangent space used by normal and bump maps.
+	pub tangent: Vec3,
+	pub bitangent: Vec3,
 	pub front_face: bool,
 	pub mat: Arc<dyn Material>
 }
 
 impl HitRecord {
 	pub fn new() -> Self {
-		Self {p: Point3::new(), normal: Vec3::new(), t: 0.0, front_face: false, mat: blank_material()}
+		Self {p: Point3::new(), normal: Vec3::new(), t: 0.0, u: 0.0, v: 0.0, tangent: Vec3::new(), bitangent: Vec3::new(), front_face: false, mat: blank_material()}
 	}
 
 	pub fn set_face_normal(&mut self, r: &Ray, outward_normal: &Vec3) {
 		self.front_face = Vec3::dot(&r.direction(), &outward_normal) < 0.0;
 		self.normal = if self.front_face {*outward_normal} else {-*outward_normal}
 	}
+
+	/// Whether `r` should pass through this hit because the material's alpha says the surface isn't there.
+	/// Random for partial alpha, so shapes call it once per candidate hit and move on to the next one if true.
+	pub fn is_cut_out(&self, r: &Ray) -> bool {
+		if r.ignores_alpha() {
+			return false
+		}
+		let alpha = self.mat.alpha(self);
+		alpha < 1.0 && random_f64() >= alpha
+	}
+}
+
+/// A stretch of a ray inside a solid shape, from the hit where it enters to the one where it leaves
+#[derive(Clone)]
+pub struct Span {
+	pub enter: HitRecord,
+	pub exit: HitRecord,
 }
 
 /// A trait representing anything a ray can hit
 pub trait Hittable: Send + Sync {
 	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool;
+
+	/// Like `hit`, but participating media are skipped instead of randomly stopping the ray.
+	/// Used for shadow rays, together with `transmittance` for how much the media let through.
+	fn hit_solid(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		self.hit(r, ray_t, rec)
+	}
+
+	/// Every stretch of the whole line through `r` that's inside the shape, in order, including any behind its origin.
+	/// Used by `Csg` to combine solids. Anything without spans disappears from a combination, that's shapes
+	/// that don't enclose anything but also ones that don't report them, like a `HittableList` or a mesh.
+	fn spans(&self, _r: &Ray) -> Vec<Span> {
+		Vec::new()
+	}
+
+	/// Fraction of light that makes it along `r` within `ray_t` through participating media.
+	/// Solid objects either block the ray or not, so they let everything through here.
+	fn transmittance(&self, _r: &Ray, _ray_t: &Interval) -> Color {
+		Color::from_xyz(1.0, 1.0, 1.0)
+	}
+
+	/// Solid angle density of `random` picking `direction` from `origin`.
+	/// Only needs implementing for shapes that can be sampled as lights.
+	fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+		0.0
+	}
+
+	/// Returns a random direction from `origin` towards this object
+	fn random(&self, _origin: &Point3) -> Vec3 {
+		Vec3::from_xyz(1.0, 0.0, 0.0)
+	}
+
+	/// Picks a point uniformly on the surface, filling in its position, outward normal and material,
+	/// and returns the area density of having picked it. Zero if the shape can't be sampled.
+	fn sample_surface(&self, _rec: &mut HitRecord) -> f64 {
+		0.0
+	}
+
+	/// Area density with which `sample_surface` picks `point`, zero if it doesn't lie on the surface
+	fn surface_pdf(&self, _point: &Point3) -> f64 {
+		0.0
+	}
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::{hittable::{HitRecord, Hittable}, interval::Interval};
+use crate::{color::Color, hittable::{HitRecord, Hittable}, interval::Interval, rand::rand_range, vec3::{Point3, Vec3}};
 
 pub struct HittableList
 {
@@ -39,4 +39,61 @@ impl Hittable for HittableList {
 
 		hit_anything
 	}
+
+	fn hit_solid(&self, r: &crate::ray::Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		let mut temp_rec = HitRecord::new();
+		let mut hit_anything = false;
+		let mut closest_so_far = ray_t.max;
+
+		for object in &self.objects {
+			if object.hit_solid(r, &Interval::from_values(ray_t.min, closest_so_far), &mut temp_rec) {
+				hit_anything = true;
+				closest_so_far = temp_rec.t;
+				*rec = temp_rec.clone();
+			}
+		}
+
+		hit_anything
+	}
+
+	fn transmittance(&self, r: &crate::ray::Ray, ray_t: &Interval) -> Color {
+		let mut transmittance = Color::from_xyz(1.0, 1.0, 1.0);
+		for object in &self.objects {
+			transmittance = transmittance * object.transmittance(r, ray_t);
+		}
+		transmittance
+	}
+
+	fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+		if self.objects.is_empty() {
+			return 0.0
+		}
+
+		// Each object is picked with equal probability by `random`
+		let weight = 1.0/self.objects.len() as f64;
+		self.objects.iter().map(|object| weight * object.pdf_value(origin, direction)).sum()
+	}
+
+	fn random(&self, origin: &Point3) -> Vec3 {
+		let index = rand_range(0, self.objects.len() as i32 - 1) as usize;
+		self.objects[index].random(origin)
+	}
+
+	fn sample_surface(&self, rec: &mut HitRecord) -> f64 {
+		if self.objects.is_empty() {
+			return 0.0
+		}
+
+		let index = rand_range(0, self.objects.len() as i32 - 1) as usize;
+		self.objects[index].sample_surface(rec)/self.objects.len() as f64
+	}
+
+	fn surface_pdf(&self, point: &Point3) -> f64 {
+		if self.objects.is_empty() {
+			return 0.0
+		}
+
+		let weight = 1.0/self.objects.len() as f64;
+		self.objects.iter().map(|object| weight * object.surface_pdf(point)).sum()
+	}
 }
@@ -0,0 +1,607 @@
+use std::{fs, io, path::Path};
+
+use crate::color::Color;
+
+/// An RGB image in linear color, loaded from a PNG or PPM file for use as a texture.
+/// PNGs with transparency keep their alpha channel too.
+pub struct Image {
+	pub width: usize,
+	pub height: usize,
+	// rows top to bottom, pixels left to right
+	pixels: Vec<Color>,
+	// opacity of each pixel from 0 to 1, in the same order, if the file had any
+	alpha: Option<Vec<f64>>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+impl Image {
+	pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+		assert_eq!(pixels.len(), width * height, "pixel count doesn't match the image size");
+		Self { width, height, pixels, alpha: None }
+	}
+
+	/// Adds an alpha channel, one opacity from 0 to 1 per pixel
+	pub fn with_alpha(self, alpha: Vec<f64>) -> Self {
+		assert_eq!(alpha.len(), self.width * self.height, "alpha count doesn't match the image size");
+		Self { alpha: Some(alpha), ..self }
+	}
+
+	/// Loads an 8 or 16 bit PNG (non-interlaced) or a PPM (P3 or P6) file, telling them apart by their contents
+	pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+		let bytes = fs::read(path)?;
+		if bytes.starts_with(PNG_SIGNATURE) {
+			return Self::from_png(&bytes)
+		}
+		if bytes.starts_with(b"P3") || bytes.starts_with(b"P6") {
+			return Self::from_ppm(&bytes)
+		}
+		Err(invalid("unsupported image format, expected PNG or PPM"))
+	}
+
+	/// Loads an image that holds data rather than colors, e.g a normal map, keeping the stored values instead of
+	/// converting them to linear color
+	pub fn load_data(path: impl AsRef<Path>) -> io::Result<Self> {
+		let mut image = Self::load(path)?;
+		for pixel in image.pixels.iter_mut() {
+			*pixel = Color::from_xyz(pixel.x().sqrt(), pixel.y().sqrt(), pixel.z().sqrt());
+		}
+		Ok(image)
+	}
+
+	pub fn pixel(&self, x: usize, y: usize) -> Color {
+		self.pixels[y * self.width + x]
+	}
+
+	/// Opacity of a pixel, fully opaque for images without an alpha channel
+	pub fn alpha(&self, x: usize, y: usize) -> f64 {
+		self.alpha.as_ref().map_or(1.0, |alpha| alpha[y * self.width + x])
+	}
+
+	pub fn has_alpha(&self) -> bool {
+		self.alpha.is_some()
+	}
+
+	/// Files store gamma encoded bytes, textures are looked up in the same linear space we render in.
+	/// This is the inverse of the gamma 2 transform in `write_color`.
+	fn from_bytes(width: usize, height: usize, rgb: impl Iterator<Item = [f64; 3]>) -> Self {
+		let pixels = rgb.map(|[r, g, b]| Color::from_xyz(r * r, g * g, b * b)).collect();
+		Self::new(width, height, pixels)
+	}
+
+	fn from_ppm(bytes: &[u8]) -> io::Result<Self> {
+		// The header is whitespace separated tokens, with comments running from '#' to the end of the line
+		let mut pos = 2;
+		let mut header = [0usize; 3];
+		for value in header.iter_mut() {
+			loop {
+				while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {pos += 1}
+				if pos < bytes.len() && bytes[pos] == b'#' {
+					while pos < bytes.len() && bytes[pos] != b'\n' {pos += 1}
+				} else {
+					break
+				}
+			}
+			let start = pos;
+			while pos < bytes.len() && bytes[pos].is_ascii_digit() {pos += 1}
+			*value = std::str::from_utf8(&bytes[start..pos]).ok().and_then(|s| s.parse().ok()).ok_or_else(|| invalid("bad PPM header"))?;
+		}
+
+		let [width, height, max_value] = header;
+		if max_value == 0 || max_value > 65535 {
+			return Err(invalid("bad PPM max value"))
+		}
+		let count = width.checked_mul(height).and_then(|n| n.checked_mul(3)).ok_or_else(|| invalid("PPM is too large"))?;
+		let scale = 1.0/max_value as f64;
+
+		let samples: Vec<f64> = if bytes.starts_with(b"P3") {
+			std::str::from_utf8(&bytes[pos..]).map_err(|_| invalid("bad PPM data"))?
+				.split_whitespace()
+				.take(count)
+				.map(|s| s.parse::<usize>().map(|v| v as f64 * scale).map_err(|_| invalid("bad PPM data")))
+				.collect::<io::Result<_>>()?
+		} else {
+			// exactly one whitespace byte separates the header from binary data
+			let data = bytes.get(pos + 1..).unwrap_or(&[]);
+			if max_value < 256 {
+				data.iter().take(count).map(|&b| b as f64 * scale).collect()
+			} else {
+				data.chunks_exact(2).take(count).map(|c| u16::from_be_bytes([c[0], c[1]]) as f64 * scale).collect()
+			}
+		};
+
+		if samples.len() != count {
+			return Err(invalid("PPM data is truncated"))
+		}
+
+		Ok(Self::from_bytes(width, height, samples.chunks_exact(3).map(|c| [c[0], c[1], c[2]])))
+	}
+
+	fn from_png(bytes: &[u8]) -> io::Result<Self> {
+		let mut pos = PNG_SIGNATURE.len();
+		let mut header = None;
+		let mut palette: Vec<[u8; 3]> = Vec::new();
+		let mut palette_alpha: Vec<u8> = Vec::new();
+		let mut compressed = Vec::new();
+
+		while pos + 8 <= bytes.len() {
+			let length = u32::from_be_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]]) as usize;
+			let kind = &bytes[pos + 4..pos + 8];
+			let data = bytes.get(pos + 8..pos + 8 + length).ok_or_else(|| invalid("PNG chunk is truncated"))?;
+			let crc = bytes.get(pos + 8 + length..pos + 12 + length).ok_or_else(|| invalid("PNG chunk is truncated"))?;
+			// the CRC covers the chunk type and data
+			if u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]) != crc32(&bytes[pos + 4..pos + 8 + length]) {
+				return Err(invalid("PNG chunk is corrupt"))
+			}
+			pos += 12 + length;
+
+			match kind {
+				b"IHDR" => {
+					if data.len() < 13 {
+						return Err(invalid("bad PNG header"))
+					}
+					let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+					let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+					header = Some((width, height, data[8], data[9], data[12]));
+				}
+				b"PLTE" => palette = data.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+				b"tRNS" => palette_alpha = data.to_vec(),
+				b"IDAT" => compressed.extend_from_slice(data),
+				b"IEND" => break,
+				_ => {}
+			}
+		}
+
+		let (width, height, bit_depth, color_type, interlace) = header.ok_or_else(|| invalid("PNG has no header"))?;
+		if interlace != 0 {
+			return Err(invalid("interlaced PNGs are not supported"))
+		}
+		if bit_depth != 8 && !(bit_depth == 16 && color_type != 3) {
+			return Err(invalid("only 8 and 16 bit PNGs are supported"))
+		}
+		let channels = match color_type {
+			0 => 1, // gray
+			2 => 3, // rgb
+			3 => 1, // palette
+			4 => 2, // gray + alpha
+			6 => 4, // rgba
+			_ => return Err(invalid("unknown PNG color type"))
+		};
+		let bytes_per_sample = bit_depth as usize/8;
+		let bpp = channels * bytes_per_sample;
+		let stride = width.checked_mul(bpp).ok_or_else(|| invalid("PNG is too large"))?;
+		let raw_length = (stride + 1).checked_mul(height).ok_or_else(|| invalid("PNG is too large"))?;
+
+		// A 2 byte zlib header, deflate data and an Adler-32 checksum of what it inflates to
+		let zlib_header = compressed.get(..2).ok_or_else(|| invalid("PNG data is truncated"))?;
+		if zlib_header[0] & 0x0f != 8 || u16::from_be_bytes([zlib_header[0], zlib_header[1]]) % 31 != 0 {
+			return Err(invalid("bad zlib header"))
+		}
+		let (raw, used) = inflate(&compressed[2..], raw_length)?;
+		let checksum = compressed.get(2 + used..6 + used).ok_or_else(|| invalid("PNG data is truncated"))?;
+		if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&raw) {
+			return Err(invalid("PNG data is corrupt"))
+		}
+		if raw.len() < raw_length {
+			return Err(invalid("PNG data is truncated"))
+		}
+
+		// Undo the per scanline filters
+		let mut data = vec![0u8; height * stride];
+		for y in 0..height {
+			let filter = raw[y * (stride + 1)];
+			let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+			for x in 0..stride {
+				let a = if x >= bpp {data[y * stride + x - bpp] as i32} else {0};
+				let b = if y > 0 {data[(y - 1) * stride + x] as i32} else {0};
+				let c = if x >= bpp && y > 0 {data[(y - 1) * stride + x - bpp] as i32} else {0};
+				let predictor = match filter {
+					0 => 0,
+					1 => a,
+					2 => b,
+					3 => (a + b)/2,
+					4 => {
+						let p = a + b - c;
+						let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+						if pa <= pb && pa <= pc {a} else if pb <= pc {b} else {c}
+					}
+					_ => return Err(invalid("unknown PNG filter"))
+				};
+				data[y * stride + x] = (line[x] as i32 + predictor) as u8;
+			}
+		}
+
+		let max = ((1u32 << bit_depth) - 1) as f64;
+		let sample = |pixel: &[u8], channel: usize| -> f64 {
+			let i = channel * bytes_per_sample;
+			let value = if bytes_per_sample == 2 {u16::from_be_bytes([pixel[i], pixel[i + 1]]) as f64} else {pixel[i] as f64};
+			value/max
+		};
+
+		let mut rgb = Vec::with_capacity(width * height);
+		let mut alpha = Vec::with_capacity(width * height);
+		for pixel in data.chunks_exact(bpp) {
+			alpha.push(match color_type {
+				4 => sample(pixel, 1),
+				6 => sample(pixel, 3),
+				// entries past the end of the transparency table are opaque
+				3 => palette_alpha.get(pixel[0] as usize).map_or(1.0, |&a| a as f64/255.0),
+				_ => 1.0
+			});
+			rgb.push(match color_type {
+				0 | 4 => [sample(pixel, 0); 3],
+				3 => {
+					let entry = palette.get(pixel[0] as usize).ok_or_else(|| invalid("PNG palette index out of range"))?;
+					[entry[0] as f64/255.0, entry[1] as f64/255.0, entry[2] as f64/255.0]
+				}
+				_ => [sample(pixel, 0), sample(pixel, 1), sample(pixel, 2)]
+			});
+		}
+
+		let image = Self::from_bytes(width, height, rgb.into_iter());
+		let has_alpha = color_type == 4 || color_type == 6 || (color_type == 3 && !palette_alpha.is_empty());
+		Ok(if has_alpha {image.with_alpha(alpha)} else {image})
+	}
+}
+
+const PNG_SIGNATURE: &[u8] = &[137, 80, 78, 71, 13, 10, 26, 10];
+
+/// The CRC-32 PNG chunks end with
+fn crc32(data: &[u8]) -> u32 {
+	let mut crc = 0xffff_ffffu32;
+	for &byte in data {
+		crc ^= byte as u32;
+		for _ in 0..8 {
+			crc = if crc & 1 != 0 {0xedb8_8320 ^ (crc >> 1)} else {crc >> 1};
+		}
+	}
+	!crc
+}
+
+/// The Adler-32 checksum zlib streams end with
+fn adler32(data: &[u8]) -> u32 {
+	let (mut a, mut b) = (1u32, 0u32);
+	for &byte in data {
+		a = (a + byte as u32) % 65521;
+		b = (b + a) % 65521;
+	}
+	(b << 16) | a
+}
+
+// Deflate (RFC 1951) decoding, enough to read PNG image data
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// order in which code length code lengths are stored in a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+	data: &'a [u8],
+	pos: usize,
+	bit: u32,
+}
+
+impl BitReader<'_> {
+	fn bits(&mut self, n: u32) -> io::Result<u32> {
+		let mut value = 0;
+		for i in 0..n {
+			let byte = *self.data.get(self.pos).ok_or_else(|| invalid("compressed data is truncated"))?;
+			value |= (((byte >> self.bit) & 1) as u32) << i;
+			self.bit += 1;
+			if self.bit == 8 {
+				self.bit = 0;
+				self.pos += 1;
+			}
+		}
+		Ok(value)
+	}
+
+	fn align_to_byte(&mut self) {
+		if self.bit != 0 {
+			self.bit = 0;
+			self.pos += 1;
+		}
+	}
+}
+
+/// A canonical Huffman code, decoded one bit at a time
+struct Huffman {
+	// number of codes of each length
+	counts: [u16; 16],
+	// symbols ordered by code
+	symbols: Vec<u16>,
+}
+
+impl Huffman {
+	fn new(lengths: &[u8]) -> Self {
+		let mut counts = [0u16; 16];
+		for &length in lengths {
+			counts[length as usize] += 1;
+		}
+		counts[0] = 0;
+
+		let mut offsets = [0u16; 16];
+		for length in 1..16 {
+			offsets[length] = offsets[length - 1] + counts[length - 1];
+		}
+
+		let mut symbols = vec![0; lengths.len()];
+		for (symbol, &length) in lengths.iter().enumerate() {
+			if length != 0 {
+				symbols[offsets[length as usize] as usize] = symbol as u16;
+				offsets[length as usize] += 1;
+			}
+		}
+
+		Self { counts, symbols }
+	}
+
+	fn decode(&self, reader: &mut BitReader) -> io::Result<u16> {
+		let mut code = 0i32;
+		let mut first = 0i32;
+		let mut index = 0i32;
+		for length in 1..16 {
+			code |= reader.bits(1)? as i32;
+			let count = self.counts[length] as i32;
+			if code - first < count {
+				return Ok(self.symbols[(index + code - first) as usize])
+			}
+			index += count;
+			first = (first + count) << 1;
+			code <<= 1;
+		}
+		Err(invalid("bad Huffman code"))
+	}
+}
+
+/// Inflates a deflate stream, returning the data and how many bytes of `data` the stream took up.
+/// Fails as soon as it would inflate to more than `limit` bytes, so a small stream can't fill up the memory.
+fn inflate(data: &[u8], limit: usize) -> io::Result<(Vec<u8>, usize)> {
+	let mut reader = BitReader { data, pos: 0, bit: 0 };
+	let mut out = Vec::new();
+
+	loop {
+		let last = reader.bits(1)? == 1;
+		match reader.bits(2)? {
+			0 => {
+				// stored block
+				reader.align_to_byte();
+				let header = data.get(reader.pos..reader.pos + 4).ok_or_else(|| invalid("compressed data is truncated"))?;
+				let length = u16::from_le_bytes([header[0], header[1]]);
+				if u16::from_le_bytes([header[2], header[3]]) != !length {
+					return Err(invalid("bad stored block length"))
+				}
+				let length = length as usize;
+				if out.len() + length > limit {
+					return Err(invalid("compressed data is too large"))
+				}
+				let block = data.get(reader.pos + 4..reader.pos + 4 + length).ok_or_else(|| invalid("compressed data is truncated"))?;
+				out.extend_from_slice(block);
+				reader.pos += 4 + length;
+			}
+			1 => {
+				let mut lengths = [0u8; 288];
+				lengths[..144].fill(8);
+				lengths[144..256].fill(9);
+				lengths[256..280].fill(7);
+				lengths[280..].fill(8);
+				inflate_block(&mut reader, &mut out, limit, &Huffman::new(&lengths), &Huffman::new(&[5; 30]))?;
+			}
+			2 => {
+				let (literals, distances) = read_dynamic_codes(&mut reader)?;
+				inflate_block(&mut reader, &mut out, limit, &literals, &distances)?;
+			}
+			_ => return Err(invalid("bad deflate block type"))
+		}
+
+		if last {
+			reader.align_to_byte();
+			return Ok((out, reader.pos))
+		}
+	}
+}
+
+fn read_dynamic_codes(reader: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+	let literal_count = reader.bits(5)? as usize + 257;
+	let distance_count = reader.bits(5)? as usize + 1;
+	let code_length_count = reader.bits(4)? as usize + 4;
+
+	let mut code_lengths = [0u8; 19];
+	for &i in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+		code_lengths[i] = reader.bits(3)? as u8;
+	}
+	let code_length_code = Huffman::new(&code_lengths);
+
+	let mut lengths = Vec::with_capacity(literal_count + distance_count);
+	while lengths.len() < literal_count + distance_count {
+		let symbol = code_length_code.decode(reader)?;
+		let (value, repeat) = match symbol {
+			0..=15 => (symbol as u8, 1),
+			16 => (*lengths.last().ok_or_else(|| invalid("bad code lengths"))?, 3 + reader.bits(2)?),
+			17 => (0, 3 + reader.bits(3)?),
+			_ => (0, 11 + reader.bits(7)?)
+		};
+		for _ in 0..repeat {
+			lengths.push(value);
+		}
+	}
+	if lengths.len() != literal_count + distance_count {
+		return Err(invalid("bad code lengths"))
+	}
+
+	Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize, literals: &Huffman, distances: &Huffman) -> io::Result<()> {
+	loop {
+		let symbol = literals.decode(reader)? as usize;
+		if symbol < 256 {
+			if out.len() >= limit {
+				return Err(invalid("compressed data is too large"))
+			}
+			out.push(symbol as u8);
+			continue
+		}
+		if symbol == 256 {
+			return Ok(())
+		}
+
+		// a back reference into what was already decoded
+		let symbol = symbol - 257;
+		if symbol >= LENGTH_BASE.len() {
+			return Err(invalid("bad length code"))
+		}
+		let length = LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+		let symbol = distances.decode(reader)? as usize;
+		if symbol >= DIST_BASE.len() {
+			return Err(invalid("bad distance code"))
+		}
+		let distance = DIST_BASE[symbol] as usize + reader.bits(DIST_EXTRA[symbol] as u32)? as usize;
+		if distance > out.len() {
+			return Err(invalid("distance too far back"))
+		}
+		if out.len() + length > limit {
+			return Err(invalid("compressed data is too large"))
+		}
+
+		let start = out.len() - distance;
+		for i in 0..length {
+			out.push(out[start + i]);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+		let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+		chunk.extend_from_slice(kind);
+		chunk.extend_from_slice(data);
+		chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+		chunk
+	}
+
+	/// An 8 bit PNG with the scanlines in a single stored (uncompressed) deflate block
+	fn stored_png(width: u32, height: u32, color_type: u8, scanlines: &[u8]) -> Vec<u8> {
+		let mut header = width.to_be_bytes().to_vec();
+		header.extend_from_slice(&height.to_be_bytes());
+		header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+		let length = scanlines.len() as u16;
+		let mut zlib = vec![0x78, 0x01, 0x01];
+		zlib.extend_from_slice(&length.to_le_bytes());
+		zlib.extend_from_slice(&(!length).to_le_bytes());
+		zlib.extend_from_slice(scanlines);
+		zlib.extend_from_slice(&adler32(scanlines).to_be_bytes());
+
+		let mut png = PNG_SIGNATURE.to_vec();
+		png.extend(chunk(b"IHDR", &header));
+		png.extend(chunk(b"IDAT", &zlib));
+		png.extend(chunk(b"IEND", &[]));
+		png
+	}
+
+	fn assert_pixel(image: &Image, x: usize, y: usize, rgb: [u8; 3]) {
+		let expected = rgb.map(|v| (v as f64/255.0).powi(2));
+		let pixel = image.pixel(x, y);
+		for (actual, expected) in [pixel.x(), pixel.y(), pixel.z()].into_iter().zip(expected) {
+			assert!((actual - expected).abs() < 1e-12, "pixel ({x}, {y}) is {pixel:?}, expected {rgb:?}");
+		}
+	}
+
+	#[test]
+	fn stored_png_round_trip() {
+		// RGBA, the second row using the Sub filter
+		let scanlines = [
+			0, 255, 0, 0, 255, 0, 128, 0, 64,
+			1, 10, 20, 30, 255, 5, 5, 5, 0,
+		];
+		let image = Image::from_png(&stored_png(2, 2, 6, &scanlines)).unwrap();
+		assert_eq!((image.width, image.height), (2, 2));
+		assert_pixel(&image, 0, 0, [255, 0, 0]);
+		assert_pixel(&image, 1, 0, [0, 128, 0]);
+		assert_pixel(&image, 0, 1, [10, 20, 30]);
+		assert_pixel(&image, 1, 1, [15, 25, 35]);
+		assert_eq!(image.alpha(0, 0), 1.0);
+		assert!((image.alpha(1, 0) - 64.0/255.0).abs() < 1e-12);
+		assert_eq!(image.alpha(1, 1), 1.0);
+	}
+
+	#[test]
+	fn fixed_huffman_png() {
+		// 2x2 RGB written by zlib, which picks a fixed Huffman block for data this small
+		let png = [
+			137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0, 253, 212, 154, 115,
+			0, 0, 0, 21, 73, 68, 65, 84, 120, 218, 99, 248, 207, 192, 192, 240, 159, 129, 17, 72, 112, 113, 113, 1, 0, 26, 55, 3, 29,
+			186, 4, 250, 30, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
+		];
+		let image = Image::from_png(&png).unwrap();
+		assert_pixel(&image, 0, 0, [255, 0, 0]);
+		assert_pixel(&image, 1, 0, [0, 255, 0]);
+		assert_pixel(&image, 0, 1, [0, 0, 255]);
+		assert_pixel(&image, 1, 1, [10, 10, 9]);
+		assert!(!image.has_alpha());
+	}
+
+	#[test]
+	fn corrupt_png_is_rejected() {
+		let scanlines = [0, 1, 2, 3];
+		let mut png = stored_png(1, 1, 2, &scanlines);
+		// a pixel byte inside the IDAT data, which breaks its CRC
+		let last_pixel = png.len() - 12 - 4 - 4 - 1;
+		png[last_pixel] ^= 0xff;
+		assert_eq!(Image::from_png(&png).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+	}
+
+	#[test]
+	fn bad_stored_block_length_is_rejected() {
+		// LEN 2 with an NLEN that doesn't match it
+		let data = [0x01, 2, 0, 0xfd, 0xfe, 7, 7];
+		assert_eq!(inflate(&data, 10).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+		let data = [0x01, 2, 0, 0xfd, 0xff, 7, 7];
+		assert_eq!(inflate(&data, 10).unwrap(), (vec![7, 7], 7));
+	}
+
+	#[test]
+	fn inflating_past_the_image_is_rejected() {
+		assert!(inflate(&[0x01, 2, 0, 0xfd, 0xff, 7, 7], 1).is_err());
+		// 259 zeros compressed by zlib into a few back references, far more than the 4 bytes of a 1x1 RGB image
+		let data = [0x63, 0x60, 0x18, 0xf1, 0x00, 0x00];
+		assert_eq!(inflate(&data, 1000).unwrap().0.len(), 259);
+		assert_eq!(inflate(&data, 4).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+		let png = stored_png(1, 1, 2, &[0, 1, 2, 3, 4, 5, 6, 7]);
+		assert_eq!(Image::from_png(&png).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+	}
+
+	#[test]
+	fn oversized_png_is_rejected() {
+		let png = stored_png(u32::MAX, u32::MAX, 6, &[0]);
+		assert_eq!(Image::from_png(&png).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+	}
+
+	#[test]
+	fn ppm_round_trip() {
+		let ascii = Image::from_ppm(b"P3\n# a comment\n2 1\n255\n255 0 0  0 51 102\n").unwrap();
+		assert_pixel(&ascii, 0, 0, [255, 0, 0]);
+		assert_pixel(&ascii, 1, 0, [0, 51, 102]);
+
+		let mut binary = b"P6 2 1 255\n".to_vec();
+		binary.extend_from_slice(&[255, 0, 0, 0, 51, 102]);
+		let binary = Image::from_ppm(&binary).unwrap();
+		assert_pixel(&binary, 0, 0, [255, 0, 0]);
+		assert_pixel(&binary, 1, 0, [0, 51, 102]);
+	}
+
+	#[test]
+	fn oversized_ppm_is_rejected() {
+		let ppm = format!("P6 {} {} 255\n", usize::MAX, 2);
+		assert_eq!(Image::from_ppm(ppm.as_bytes()).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+	}
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use crate::{hittable::{HitRecord, Hittable}, interval::Interval, material::Material, onb::Onb, polynomial::{multiply, roots_in}, ray::Ray, vec3::{Point3, Vec3}};
+
+// Parallel ray epsilon
+const E: f64 = 1e-12;
+
+/// One term `coefficient · x^i · y^j · z^k` of an implicit surface's polynomial
+#[derive(Clone, Copy)]
+pub struct Term {
+	pub coefficient: f64,
+	pub powers: [u32; 3],
+}
+
+impl Term {
+	pub fn new(coefficient: f64, i: u32, j: u32, k: u32) -> Self {
+		Self { coefficient, powers: [i, j, k] }
+	}
+}
+
+/// The surface where a polynomial in x, y and z is zero, e.g a quadric like a hyperboloid or a paraboloid,
+/// or a quartic like a Steiner surface or a tangle cube. Coordinates are relative to `center`, and the surface
+/// is clipped to the box reaching `half_size` from it on each axis, since many of them go on forever.
+///
+/// Where the polynomial is negative counts as inside, so the normal points the way it grows.
+/// Texture coordinates are the position across the box along x and z, which suits surfaces seen from above,
+/// solid textures work everywhere.
+pub struct ImplicitSurface {
+	center: Point3,
+	half_size: Vec3,
+	terms: Vec<Term>,
+	mat: Arc<dyn Material>,
+}
+
+impl ImplicitSurface {
+	pub fn new(center: &Point3, half_size: &Vec3, terms: Vec<Term>, mat: Arc<dyn Material>) -> Self {
+		Self { center: *center, half_size: *half_size, terms, mat }
+	}
+
+	/// The general quadric Ax² + By² + Cz² + Dxy + Exz + Fyz + Gx + Hy + Iz + J, from `[A, B, ..., J]`
+	pub fn quadric(center: &Point3, half_size: &Vec3, coefficients: [f64; 10], mat: Arc<dyn Material>) -> Self {
+		let powers = [[2, 0, 0], [0, 2, 0], [0, 0, 2], [1, 1, 0], [1, 0, 1], [0, 1, 1], [1, 0, 0], [0, 1, 0], [0, 0, 1], [0, 0, 0]];
+		let terms = coefficients.iter().zip(powers).map(|(coefficient, powers)| Term { coefficient: *coefficient, powers }).collect();
+		Self::new(center, half_size, terms, mat)
+	}
+
+	/// Gradient of the polynomial at `p`, relative to the center
+	fn gradient(&self, p: &Vec3) -> Vec3 {
+		let mut gradient = [0.0; 3];
+		for term in &self.terms {
+			for (axis, g) in gradient.iter_mut().enumerate() {
+				if term.powers[axis] == 0 {
+					continue
+				}
+				let mut value = term.coefficient * term.powers[axis] as f64;
+				for (other, power) in term.powers.iter().enumerate() {
+					let power = if other == axis {power - 1} else {*power};
+					value *= p[other].powi(power as i32);
+				}
+				*g += value;
+			}
+		}
+		Vec3::from_xyz(gradient[0], gradient[1], gradient[2])
+	}
+
+	/// Where the ray is inside the clipping box, if it gets there at all
+	fn bounds(&self, o: &Vec3, d: &Vec3) -> Option<(f64, f64)> {
+		let (mut enter, mut exit) = (f64::NEG_INFINITY, f64::INFINITY);
+		for axis in 0..3 {
+			let extent = self.half_size[axis];
+			if d[axis].abs() < E {
+				if o[axis].abs() > extent {
+					return None
+				}
+				continue
+			}
+			let (t1, t2) = ((-extent - o[axis])/d[axis], (extent - o[axis])/d[axis]);
+			enter = enter.max(t1.min(t2));
+			exit = exit.min(t1.max(t2));
+		}
+		(enter <= exit).then_some((enter, exit))
+	}
+}
+
+impl Hittable for ImplicitSurface {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		let d = r.direction();
+		let Some((enter, exit)) = self.bounds(&(r.origin() - self.center), &d) else {
+			return false
+		};
+		let (start, end) = (f64::max(ray_t.min, enter), f64::min(ray_t.max, exit));
+		if start > end {
+			return false
+		}
+
+		// Each term becomes a polynomial along the ray, starting from where it enters the box so the coefficients stay small
+		let o = r.at(start) - self.center;
+		let mut coefficients: Vec<f64> = Vec::new();
+		for term in &self.terms {
+			let mut product = vec![term.coefficient];
+			for axis in 0..3 {
+				for _ in 0..term.powers[axis] {
+					product = multiply(&product, &[o[axis], d[axis]]);
+				}
+			}
+			if coefficients.len() < product.len() {
+				coefficients.resize(product.len(), 0.0);
+			}
+			for (c, p) in coefficients.iter_mut().zip(product) {
+				*c += p;
+			}
+		}
+
+		for s in roots_in(&coefficients, 0.0, end - start) {
+			let t = start + s;
+			if !ray_t.surrounds(t) {
+				continue
+			}
+
+			let local = o + s * d;
+			let gradient = self.gradient(&local);
+			if gradient.length_squared() == 0.0 {
+				// A singular point, like the tip of a cone, has no normal to shade with
+				continue
+			}
+			let outward_normal = Vec3::unit_vector(&gradient);
+
+			rec.t = t;
+			rec.p = r.at(t);
+			let size = 2.0 * self.half_size;
+			rec.u = (local.x() + self.half_size.x())/size.x();
+			rec.v = (local.z() + self.half_size.z())/size.z();
+			// The box's x and z axes moved onto the surface along the normal,
+			// or any frame around the normal where it's along one of them and that one flattens out
+			let x_axis = Vec3::from_xyz(size.x(), 0.0, 0.0);
+			let z_axis = Vec3::from_xyz(0.0, 0.0, size.z());
+			rec.tangent = x_axis - Vec3::dot(&x_axis, &outward_normal) * outward_normal;
+			rec.bitangent = z_axis - Vec3::dot(&z_axis, &outward_normal) * outward_normal;
+			if rec.tangent.near_zero() || rec.bitangent.near_zero() {
+				let uvw = Onb::new(&outward_normal);
+				(rec.tangent, rec.bitangent) = (size.x() * uvw.u(), size.z() * uvw.v());
+			}
+			rec.set_face_normal(r, &outward_normal);
+			rec.mat = self.mat.clone();
+
+			if !rec.is_cut_out(r) {
+				return true
+			}
+		}
+
+		false
+	}
+}
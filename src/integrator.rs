@@ -0,0 +1,409 @@
+use std::sync::Arc;
+
+use crate::{bdpt::Bidirectional, camera::Camera, color::Color, film::Film, hittable::{HitRecord, Hittable}, interval::Interval, photon_map::PhotonMapper, rand::random_f64, ray::Ray, scene::Scene, spectrum::{rgb_to_spectrum, sample_to_rgb, sample_wavelength}, toon::Toon, vec3::{random_unit_vector, Vec3}};
+
+/// A light transport algorithm, i.e a way of turning camera rays into colors
+pub trait Integrator: Send + Sync {
+	/// Returns the radiance carried back along `r`, following at most `depth` bounces
+	fn ray_color(&self, r: &Ray, depth: i32, scene: &Scene) -> Color;
+
+	/// Called once by `Camera::render` after the camera is set up and before any pixel is traced
+	fn preprocess(&self, _scene: &Scene, _cam: &Camera) {}
+
+	/// Estimates the color of pixel (i, j) from a single sample.
+	/// Integrators that trace from the lights too can splat onto other pixels of the film.
+	fn sample_pixel(&self, cam: &Camera, i: i32, j: i32, scene: &Scene, _film: &Film) -> Color {
+		self.ray_color(&cam.get_ray(i, j), cam.max_depth, scene)
+	}
+}
+
+/// Names accepted by `from_name`, e.g on the command line
+pub const INTEGRATOR_NAMES: [&str; 10] = ["path", "spectral", "whitted", "ao", "direct", "normals", "depth", "bdpt", "photon", "toon"];
+
+/// Looks up an integrator with default settings by name
+pub fn from_name(name: &str) -> Option<Arc<dyn Integrator>> {
+	let integrator: Arc<dyn Integrator> = match name {
+		"path" => Arc::new(PathTracer::new()),
+		"spectral" => Arc::new(SpectralPathTracer::new()),
+		"whitted" => Arc::new(Whitted::new()),
+		"ao" => Arc::new(AmbientOcclusion::new(16, 1.0)),
+		"direct" => Arc::new(DirectLighting::new()),
+		"normals" => Arc::new(Normals::new()),
+		"depth" => Arc::new(Depth::new(20.0)),
+		"bdpt" => Arc::new(Bidirectional::new()),
+		"photon" => Arc::new(PhotonMapper::new(200_000, 200_000, 64, 0.5)),
+		"toon" => Arc::new(Toon::new()),
+		_ => return None
+	};
+
+	Some(integrator)
+}
+
+// Shadow acne epsilon, rays start slightly off the surface they left
+pub const T_MIN: f64 = 0.001;
+
+/// Estimates light arriving at `rec` straight from the scene's emissive objects, using a single shadow ray
+pub fn sample_lights(r: &Ray, rec: &HitRecord, scene: &Scene) -> Color {
+	if scene.lights.objects.is_empty() {
+		return Color::new()
+	}
+
+	let to_light = Ray::from_values(&rec.p, &scene.lights.random(&rec.p));
+	let pdf = scene.lights.pdf_value(&rec.p, &to_light.direction());
+	if pdf <= 0.0 {
+		return Color::new()
+	}
+
+	let f = rec.mat.eval(r, rec, &to_light);
+	if f.near_zero() {
+		return Color::new()
+	}
+
+	// Whatever solid surface the shadow ray hits first is what lights the point, occluders simply emit nothing.
+	// Volumes in between only dim the light.
+	let mut light_rec = HitRecord::new();
+	if !scene.world.hit_solid(&to_light, &Interval::from_values(T_MIN, f64::INFINITY), &mut light_rec) {
+		return Color::new()
+	}
+
+	let transmittance = scene.world.transmittance(&to_light, &Interval::from_values(T_MIN, light_rec.t));
+	f * transmittance * light_rec.mat.emitted(&to_light, &light_rec) / pdf
+}
+
+/// Light arriving at `rec` from all of the scene's delta lights, each through a single shadow ray.
+/// Nothing can hit these lights by chance, so every integrator that wants them has to add this at each surface it shades.
+pub fn sample_delta_lights(r: &Ray, rec: &HitRecord, scene: &Scene) -> Color {
+	let mut l = Color::new();
+	for light in &scene.delta_lights {
+		let Some(sample) = light.sample_li(&rec.p) else {
+			continue
+		};
+
+		let to_light = Ray::from_values(&rec.p, &sample.direction);
+		let f = rec.mat.eval(r, rec, &to_light);
+		if f.near_zero() {
+			continue
+		}
+
+		let shadow_t = Interval::from_values(T_MIN, sample.distance - T_MIN);
+		let mut occluder = HitRecord::new();
+		if scene.world.hit_solid(&to_light, &shadow_t, &mut occluder) {
+			continue
+		}
+
+		l += f * scene.world.transmittance(&to_light, &shadow_t) * sample.irradiance;
+	}
+	l
+}
+
+/// Light from the sky's sun arriving at `rec`, through a single shadow ray towards a random point on the disk.
+/// The material could also find the sun by scattering towards it, so both ways are weighed against each other
+/// with the power heuristic, see `escaped_radiance` for the other half.
+pub fn sample_sun(r: &Ray, rec: &HitRecord, scene: &Scene) -> Color {
+	let Some(sky) = &scene.sky else {
+		return Color::new()
+	};
+
+	let direction = sky.sample_sun();
+	let light_pdf = sky.sun_pdf(&direction);
+	if light_pdf <= 0.0 {
+		return Color::new()
+	}
+
+	let to_sun = Ray::from_values(&rec.p, &direction);
+	let f = rec.mat.eval(r, rec, &to_sun);
+	if f.near_zero() {
+		return Color::new()
+	}
+
+	let shadow_t = Interval::from_values(T_MIN, f64::INFINITY);
+	let mut occluder = HitRecord::new();
+	if scene.world.hit_solid(&to_sun, &shadow_t, &mut occluder) {
+		return Color::new()
+	}
+
+	let weight = power_heuristic(light_pdf, rec.mat.scattering_pdf(r, rec, &to_sun));
+	f * scene.world.transmittance(&to_sun, &shadow_t) * sky.sun_radiance(&direction) * weight/light_pdf
+}
+
+/// Radiance arriving along `r` that escaped the scene after being scattered with solid angle density `bsdf_pdf`,
+/// or 0 for camera rays and specular bounces. The sun's part is weighed against `sample_sun` having found it.
+pub fn escaped_radiance(r: &Ray, bsdf_pdf: f64, scene: &Scene) -> Color {
+	let Some(sky) = &scene.sky else {
+		return scene.background(r)
+	};
+	if bsdf_pdf <= 0.0 {
+		return sky.radiance(&r.direction())
+	}
+
+	let weight = power_heuristic(bsdf_pdf, sky.sun_pdf(&r.direction()));
+	sky.sky_radiance(&r.direction()) + weight * sky.sun_radiance(&r.direction())
+}
+
+/// MIS weight of a sample taken with density `f` against another strategy with density `g`
+fn power_heuristic(f: f64, g: f64) -> f64 {
+	if f <= 0.0 {
+		return 0.0
+	}
+	(f * f)/(f * f + g * g)
+}
+
+/// The original naive path tracer: follow a single scattered ray per bounce until it escapes or is absorbed
+#[derive(Default)]
+pub struct PathTracer;
+
+impl PathTracer {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Radiance along `r`, which was scattered in its direction with solid angle density `bsdf_pdf`
+	fn radiance(&self, r: &Ray, depth: i32, scene: &Scene, bsdf_pdf: f64) -> Color {
+		if depth <= 0 {
+			return Color::new()
+		}
+
+		let mut rec = HitRecord::new();
+
+		if !scene.world.hit(r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			return escaped_radiance(r, bsdf_pdf, scene)
+		}
+
+		let emitted = rec.mat.emitted(r, &rec) + sample_delta_lights(r, &rec, scene) + sample_sun(r, &rec, scene);
+		let mut scattered = Ray::new();
+		let mut attenuation = Color::new();
+
+		if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
+			let pdf = rec.mat.scattering_pdf(r, &rec, &scattered);
+			return emitted + attenuation * self.radiance(&scattered, depth-1, scene, pdf)
+		}
+
+		emitted
+	}
+}
+
+impl Integrator for PathTracer {
+	fn ray_color(&self, r: &Ray, depth: i32, scene: &Scene) -> Color {
+		self.radiance(r, depth, scene, 0.0)
+	}
+}
+
+/// The path tracer in spectral mode: every path carries a single randomly picked wavelength, RGB colors
+/// of materials, lights and the sky are turned into spectra along the way, and the result goes back to RGB
+/// through XYZ. Noisier in color than RGB rendering, but dispersive glass splits light into rainbows.
+#[derive(Default)]
+pub struct SpectralPathTracer;
+
+impl SpectralPathTracer {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Radiance along `r` at the ray's wavelength, `r` having been scattered with solid angle density `bsdf_pdf`
+	fn radiance(&self, r: &Ray, depth: i32, scene: &Scene, bsdf_pdf: f64) -> f64 {
+		if depth <= 0 {
+			return 0.0
+		}
+		let wavelength = r.wavelength().unwrap_or(550.0);
+
+		let mut rec = HitRecord::new();
+
+		if !scene.world.hit(r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			return rgb_to_spectrum(&escaped_radiance(r, bsdf_pdf, scene), wavelength)
+		}
+
+		let emitted = rgb_to_spectrum(&(rec.mat.emitted(r, &rec) + sample_delta_lights(r, &rec, scene) + sample_sun(r, &rec, scene)), wavelength);
+		let mut scattered = Ray::new();
+		let mut attenuation = Color::new();
+
+		if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
+			let pdf = rec.mat.scattering_pdf(r, &rec, &scattered);
+			let scattered = scattered.with_wavelength(wavelength);
+			return emitted + rgb_to_spectrum(&attenuation, wavelength) * self.radiance(&scattered, depth-1, scene, pdf)
+		}
+
+		emitted
+	}
+}
+
+impl Integrator for SpectralPathTracer {
+	fn ray_color(&self, r: &Ray, depth: i32, scene: &Scene) -> Color {
+		let (wavelength, pdf) = sample_wavelength(random_f64());
+		let radiance = self.radiance(&r.with_wavelength(wavelength), depth, scene, 0.0);
+		sample_to_rgb(radiance, wavelength, pdf)
+	}
+}
+
+/// Classic Whitted-style ray tracing: recursive mirror and glass rays,
+/// while diffuse surfaces only see direct light (with hard shadows) plus an ambient term from the sky
+#[derive(Default)]
+pub struct Whitted;
+
+impl Whitted {
+	pub fn new() -> Self {
+		Self
+	}
+}
+
+impl Integrator for Whitted {
+	fn ray_color(&self, r: &Ray, depth: i32, scene: &Scene) -> Color {
+		if depth <= 0 {
+			return Color::new()
+		}
+
+		let mut rec = HitRecord::new();
+
+		if !scene.world.hit(r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			return scene.background(r)
+		}
+
+		let emitted = rec.mat.emitted(r, &rec);
+		let mut scattered = Ray::new();
+		let mut attenuation = Color::new();
+
+		if !rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
+			return emitted
+		}
+
+		if rec.mat.scattering_pdf(r, &rec, &scattered) <= 0.0 {
+			// specular, keep following the ray
+			return emitted + attenuation * self.ray_color(&scattered, depth-1, scene)
+		}
+
+		// diffuse, the sky straight above the surface stands in for all indirect light
+		let ambient = attenuation * scene.background(&Ray::from_values(&rec.p, &rec.normal));
+		emitted + ambient + sample_lights(r, &rec, scene) + sample_delta_lights(r, &rec, scene) + sample_sun(r, &rec, scene)
+	}
+}
+
+/// Single bounce lighting: emitters are sampled explicitly and the sky through the material,
+/// but light bouncing between surfaces is ignored. Specular surfaces are still followed.
+#[derive(Default)]
+pub struct DirectLighting;
+
+impl DirectLighting {
+	pub fn new() -> Self {
+		Self
+	}
+}
+
+impl Integrator for DirectLighting {
+	fn ray_color(&self, r: &Ray, depth: i32, scene: &Scene) -> Color {
+		if depth <= 0 {
+			return Color::new()
+		}
+
+		let mut rec = HitRecord::new();
+
+		if !scene.world.hit(r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			return scene.background(r)
+		}
+
+		let emitted = rec.mat.emitted(r, &rec);
+		let mut scattered = Ray::new();
+		let mut attenuation = Color::new();
+
+		if !rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
+			return emitted
+		}
+
+		let pdf = rec.mat.scattering_pdf(r, &rec, &scattered);
+		if pdf <= 0.0 {
+			return emitted + attenuation * self.ray_color(&scattered, depth-1, scene)
+		}
+
+		// Only count the scattered ray if it reaches the sky, emitters were already sampled above
+		let mut sky = Color::new();
+		let mut sky_rec = HitRecord::new();
+		if !scene.world.hit(&scattered, &Interval::from_values(T_MIN, f64::INFINITY), &mut sky_rec) {
+			sky = attenuation * escaped_radiance(&scattered, pdf, scene);
+		}
+
+		emitted + sample_lights(r, &rec, scene) + sample_delta_lights(r, &rec, scene) + sample_sun(r, &rec, scene) + sky
+	}
+}
+
+/// Ambient occlusion: the fraction of the hemisphere above the first hit that is not blocked within `radius`
+pub struct AmbientOcclusion {
+	samples: i32,
+	radius: f64
+}
+
+impl AmbientOcclusion {
+	pub fn new(samples: i32, radius: f64) -> Self {
+		Self { samples: samples.max(1), radius }
+	}
+}
+
+impl Integrator for AmbientOcclusion {
+	fn ray_color(&self, r: &Ray, _depth: i32, scene: &Scene) -> Color {
+		let mut rec = HitRecord::new();
+
+		if !scene.world.hit(r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			return Color::from_xyz(1.0, 1.0, 1.0)
+		}
+
+		let mut unoccluded = 0;
+		for _ in 0..self.samples {
+			// cosine weighted, so the plain average is the cosine weighted visibility
+			let mut direction = rec.normal + random_unit_vector();
+			if direction.near_zero() {direction = rec.normal}
+
+			let occlusion_ray = Ray::from_values(&rec.p, &Vec3::unit_vector(&direction));
+			let mut occluder = HitRecord::new();
+			if !scene.world.hit(&occlusion_ray, &Interval::from_values(T_MIN, self.radius), &mut occluder) {
+				unoccluded += 1;
+			}
+		}
+
+		let a = unoccluded as f64/self.samples as f64;
+		Color::from_xyz(a, a, a)
+	}
+}
+
+/// Debug view of the surface normals at the first hit, mapped from [-1, 1] to [0, 1]
+#[derive(Default)]
+pub struct Normals;
+
+impl Normals {
+	pub fn new() -> Self {
+		Self
+	}
+}
+
+impl Integrator for Normals {
+	fn ray_color(&self, r: &Ray, _depth: i32, scene: &Scene) -> Color {
+		let mut rec = HitRecord::new();
+
+		if !scene.world.hit(r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			return Color::new()
+		}
+
+		0.5 * (rec.normal + Color::from_xyz(1.0, 1.0, 1.0))
+	}
+}
+
+/// Debug view of the distance to the first hit, white up close fading to black at `far`
+pub struct Depth {
+	far: f64
+}
+
+impl Depth {
+	pub fn new(far: f64) -> Self {
+		Self { far }
+	}
+}
+
+impl Integrator for Depth {
+	fn ray_color(&self, r: &Ray, _depth: i32, scene: &Scene) -> Color {
+		let mut rec = HitRecord::new();
+
+		if !scene.world.hit(r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			return Color::new()
+		}
+
+		let distance = rec.t * r.direction().length();
+		let a = Interval::from_values(0.0, 1.0).clamp(1.0 - distance/self.far);
+		Color::from_xyz(a, a, a)
+	}
+}
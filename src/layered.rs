@@ -0,0 +1,206 @@
+use std::sync::Arc;
+
+use crate::{color::Color, hittable::HitRecord, material::Material, microfacet::{fresnel_dielectric, local_directions, reflect, TrowbridgeReitz}, onb::Onb, rand::random_f64, ray::Ray, texture::{SolidColor, Texture}, vec3::Vec3};
+
+/// Blends two materials, e.g dust on metal or moss on stone. The average of `weight`'s channels
+/// is how much of `b` there is at a point, the rest is `a`.
+pub struct MixMaterial {
+	a: Arc<dyn Material>,
+	b: Arc<dyn Material>,
+	weight: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+	pub fn new(a: Arc<dyn Material>, b: Arc<dyn Material>, weight: f64) -> Self {
+		Self::from_texture(a, b, Arc::new(SolidColor::from_rgb(weight, weight, weight)))
+	}
+
+	pub fn from_texture(a: Arc<dyn Material>, b: Arc<dyn Material>, weight: Arc<dyn Texture>) -> Self {
+		Self { a, b, weight }
+	}
+
+	fn weight(&self, rec: &HitRecord) -> f64 {
+		let w = self.weight.value(rec.u, rec.v, &rec.p);
+		((w.x() + w.y() + w.z())/3.0).clamp(0.0, 1.0)
+	}
+}
+
+impl Material for MixMaterial {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		let t = self.weight(rec);
+		let chosen = if random_f64() < t {&self.b} else {&self.a};
+		if !chosen.scatter(r_in, rec, attenuation, scattered) {
+			return false
+		}
+
+		// A specular direction can't be evaluated by the other material, so it only counts
+		// as often as its material gets picked, which is already accounted for by picking it
+		if chosen.scattering_pdf(r_in, rec, scattered) <= 0.0 {
+			*scattered = scattered.as_specular();
+			return true
+		}
+
+		let pdf = self.scattering_pdf(r_in, rec, scattered);
+		if pdf <= 0.0 {
+			return false
+		}
+		*attenuation = self.eval(r_in, rec, scattered)/pdf;
+		true
+	}
+
+	fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+		let t = self.weight(rec);
+		(1.0 - t) * self.a.emitted(r_in, rec) + t * self.b.emitted(r_in, rec)
+	}
+
+	fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		let t = self.weight(rec);
+		(1.0 - t) * self.a.eval(r_in, rec, scattered) + t * self.b.eval(r_in, rec, scattered)
+	}
+
+	fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+		if scattered.is_specular() {
+			return 0.0
+		}
+		let t = self.weight(rec);
+		(1.0 - t) * self.a.scattering_pdf(r_in, rec, scattered) + t * self.b.scattering_pdf(r_in, rec, scattered)
+	}
+
+	fn alpha(&self, rec: &HitRecord) -> f64 {
+		let t = self.weight(rec);
+		(1.0 - t) * self.a.alpha(rec) + t * self.b.alpha(rec)
+	}
+}
+
+/// A clear dielectric layer, e.g varnish or lacquer, over any other material.
+/// The coat reflects off GGX microfacets by its Fresnel term, and what it lets through in both directions
+/// lights the base, optionally tinted by the coat's color. Light bouncing around inside the coat and the
+/// bending of directions going through it are ignored.
+///
+/// Only the outside is coated, from inside the object the base material is all there is.
+pub struct Coated {
+	base: Arc<dyn Material>,
+	ior: f64,
+	distribution: TrowbridgeReitz,
+	// Fraction of light making it down to the base and back up at normal incidence
+	tint: Color,
+}
+
+impl Coated {
+	pub fn new(base: Arc<dyn Material>, ior: f64, roughness: f64) -> Self {
+		Self::tinted(base, ior, roughness, Color::from_xyz(1.0, 1.0, 1.0))
+	}
+
+	/// A colored coat, e.g amber varnish
+	pub fn tinted(base: Arc<dyn Material>, ior: f64, roughness: f64, tint: Color) -> Self {
+		Self { base, ior, distribution: TrowbridgeReitz::new(roughness), tint }
+	}
+
+	/// Fraction of light making it through the coat to the base and back out, at the given cosines
+	fn transmittance(&self, cos_o: f64, cos_i: f64) -> Color {
+		let through = (1.0 - fresnel_dielectric(cos_o, self.ior)) * (1.0 - fresnel_dielectric(cos_i, self.ior));
+		// Beer-Lambert, the tint is for a path of twice the coat's thickness straight down and up
+		let length = 0.5 * (1.0/cos_o + 1.0/cos_i);
+		let absorbed = Color::from_xyz(self.tint.x().powf(length), self.tint.y().powf(length), self.tint.z().powf(length));
+		through * absorbed
+	}
+
+	fn coat_eval(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+		let wm = Vec3::unit_vector(&(*wo + *wi));
+		fresnel_dielectric(Vec3::dot(wo, &wm), self.ior) * self.distribution.d(&wm) * self.distribution.g(wo, wi)/(4.0 * wo.z())
+	}
+
+	fn coat_pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+		let wm = Vec3::unit_vector(&(*wo + *wi));
+		self.distribution.visible_d(wo, &wm)/(4.0 * Vec3::dot(wo, &wm).abs())
+	}
+}
+
+impl Material for Coated {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		if !rec.front_face {
+			return self.base.scatter(r_in, rec, attenuation, scattered)
+		}
+
+		let uvw = Onb::new(&rec.normal);
+		let wo = uvw.to_local(&-Vec3::unit_vector(&r_in.direction()));
+		if wo.z() <= 0.0 {
+			return false
+		}
+
+		// Pick the coat as often as it reflects head on
+		let p_coat = fresnel_dielectric(wo.z(), self.ior);
+		if random_f64() < p_coat {
+			let wi = reflect(&wo, &self.distribution.sample_wm(&wo));
+			if wi.z() <= 0.0 {
+				return false
+			}
+			*scattered = Ray::from_values(&rec.p, &uvw.transform(&wi));
+		} else {
+			if !self.base.scatter(r_in, rec, attenuation, scattered) {
+				return false
+			}
+			if self.base.scattering_pdf(r_in, rec, scattered) <= 0.0 {
+				// A specular base, weigh it by what gets through the coat instead
+				let wi = uvw.to_local(&Vec3::unit_vector(&scattered.direction()));
+				if wi.z() <= 0.0 {
+					return false
+				}
+				*attenuation = *attenuation * self.transmittance(wo.z(), wi.z())/(1.0 - p_coat);
+				*scattered = scattered.as_specular();
+				return true
+			}
+		}
+
+		let pdf = self.scattering_pdf(r_in, rec, scattered);
+		if pdf <= 0.0 {
+			return false
+		}
+		*attenuation = self.eval(r_in, rec, scattered)/pdf;
+		true
+	}
+
+	fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+		let emitted = self.base.emitted(r_in, rec);
+		if !rec.front_face {
+			return emitted
+		}
+		let cos_o = Vec3::dot(&-Vec3::unit_vector(&r_in.direction()), &rec.normal).clamp(0.0, 1.0);
+		(1.0 - fresnel_dielectric(cos_o, self.ior)) * emitted
+	}
+
+	fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		let base = self.base.eval(r_in, rec, scattered);
+		if !rec.front_face {
+			return base
+		}
+
+		let (wo, wi) = local_directions(r_in, rec, scattered);
+		if wo.z() <= 0.0 || wi.z() <= 0.0 {
+			return Color::new()
+		}
+		let coat = self.coat_eval(&wo, &wi);
+		Color::from_xyz(coat, coat, coat) + self.transmittance(wo.z(), wi.z()) * base
+	}
+
+	fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+		let base = self.base.scattering_pdf(r_in, rec, scattered);
+		if !rec.front_face {
+			return base
+		}
+		if scattered.is_specular() {
+			return 0.0
+		}
+
+		let (wo, wi) = local_directions(r_in, rec, scattered);
+		if wo.z() <= 0.0 || wi.z() <= 0.0 {
+			return 0.0
+		}
+		let p_coat = fresnel_dielectric(wo.z(), self.ior);
+		p_coat * self.coat_pdf(&wo, &wi) + (1.0 - p_coat) * base
+	}
+
+	fn alpha(&self, rec: &HitRecord) -> f64 {
+		self.base.alpha(rec)
+	}
+}
@@ -0,0 +1,122 @@
+use crate::{color::Color, vec3::{Point3, Vec3}};
+
+/// Light reaching a point from a light, before checking whether anything is in the way
+pub struct LightSample {
+	// Unit vector from the point towards the light
+	pub direction: Vec3,
+	// How far away the light is along `direction`, infinite for directional lights
+	pub distance: f64,
+	// Irradiance arriving at a surface facing the light, falloff with distance included
+	pub irradiance: Color,
+}
+
+/// Lights without any geometry, which only shine from a single point or a single direction.
+/// Rays can never hit them, so integrators reach them with shadow rays from every surface they light.
+/// They go in `Scene::delta_lights`, not in the world.
+///
+/// Photons and light subpaths are only emitted from area lights, so the photon integrator only gets the direct
+/// light of delta lights, and bdpt misses the caustics they cast.
+pub trait Light: Send + Sync {
+	/// The light's contribution at `p`, or None if it doesn't shine there at all
+	fn sample_li(&self, p: &Point3) -> Option<LightSample>;
+}
+
+/// A bare light bulb, shining equally in all directions and falling off with the square of the distance
+pub struct PointLight {
+	position: Point3,
+	// Radiant intensity, what arrives at a distance of 1
+	intensity: Color,
+}
+
+impl PointLight {
+	pub fn new(position: Point3, intensity: Color) -> Self {
+		Self { position, intensity }
+	}
+}
+
+impl Light for PointLight {
+	fn sample_li(&self, p: &Point3) -> Option<LightSample> {
+		let to_light = self.position - *p;
+		let distance_squared = to_light.length_squared();
+		if distance_squared <= 0.0 {
+			return None
+		}
+
+		Some(LightSample { direction: to_light/distance_squared.sqrt(), distance: distance_squared.sqrt(), irradiance: self.intensity/distance_squared })
+	}
+}
+
+/// A point light limited to a cone, like a stage light or a torch. It is at full strength within
+/// `inner_angle` of its axis and fades out smoothly towards `outer_angle`, both in degrees from the axis.
+pub struct SpotLight {
+	position: Point3,
+	// Unit vector along the axis of the cone, pointing away from the light
+	axis: Vec3,
+	intensity: Color,
+	cos_inner: f64,
+	cos_outer: f64,
+}
+
+impl SpotLight {
+	pub fn new(position: Point3, target: Point3, intensity: Color, inner_angle: f64, outer_angle: f64) -> Self {
+		let outer_angle = outer_angle.clamp(0.0, 180.0);
+		let inner_angle = inner_angle.clamp(0.0, outer_angle);
+		Self {
+			position,
+			axis: Vec3::unit_vector(&(target - position)),
+			intensity,
+			cos_inner: inner_angle.to_radians().cos(),
+			cos_outer: outer_angle.to_radians().cos(),
+		}
+	}
+
+	/// Fraction of the intensity going out at `cos_theta` from the axis
+	fn falloff(&self, cos_theta: f64) -> f64 {
+		if cos_theta >= self.cos_inner {
+			return 1.0
+		}
+		if cos_theta <= self.cos_outer {
+			return 0.0
+		}
+		let t = (cos_theta - self.cos_outer)/(self.cos_inner - self.cos_outer);
+		t * t * (3.0 - 2.0 * t)
+	}
+}
+
+impl Light for SpotLight {
+	fn sample_li(&self, p: &Point3) -> Option<LightSample> {
+		let to_light = self.position - *p;
+		let distance_squared = to_light.length_squared();
+		if distance_squared <= 0.0 {
+			return None
+		}
+		let direction = to_light/distance_squared.sqrt();
+
+		let falloff = self.falloff(Vec3::dot(&-direction, &self.axis));
+		if falloff <= 0.0 {
+			return None
+		}
+
+		Some(LightSample { direction, distance: distance_squared.sqrt(), irradiance: falloff * self.intensity/distance_squared })
+	}
+}
+
+/// Light from so far away that it arrives in parallel everywhere, e.g the sun
+pub struct DirectionalLight {
+	// Unit vector towards the light
+	direction: Vec3,
+	irradiance: Color,
+}
+
+impl DirectionalLight {
+	/// `direction` points from the scene towards the light, `irradiance` is what arrives on a surface facing it
+	pub fn new(direction: Vec3, irradiance: Color) -> Self {
+		Self { direction: Vec3::unit_vector(&direction), irradiance }
+	}
+}
+
+impl Light for DirectionalLight {
+	fn sample_li(&self, _p: &Point3) -> Option<LightSample> {
+		Some(LightSample { direction: self.direction, distance: f64::INFINITY, irradiance: self.irradiance })
+	}
+}
@@ -1,6 +1,6 @@
 use core::f64;
 use std::{f64::consts::PI, sync::Arc};
-use crate::{camera::Camera, color::{write_color, Color}, cube::Cube, hittable::{HitRecord, Hittable}, hittable_list::HittableList, interval::Interval, material::{Dielectric, Lambertian, Metal}, rand::{random_f64, random_f64_range}, ray::Ray, sphere::Sphere, vec3::{Point3, Vec3}};
+use crate::{camera::Camera, color::{write_color, Color}, cube::Cube, hittable::{HitRecord, Hittable}, hittable_list::HittableList, integrator::{Integrator, PathTracer, INTEGRATOR_NAMES}, interval::Interval, material::{Dielectric, Lambertian, Metal}, plane::Plane, rand::{random_f64, random_f64_range}, ray::Ray, scene::Scene, sphere::Sphere, vec3::{Point3, Vec3}};
 
 
 pub mod vec3;
@@ -14,11 +14,43 @@ pub mod cube;
 pub mod interval;
 pub mod rand;
 pub mod material;
+pub mod onb;
+pub mod scene;
+pub mod integrator;
+pub mod film;
+pub mod bdpt;
+pub mod photon_map;
+pub mod constant_medium;
+pub mod grid_medium;
+pub mod image;
+pub mod texture;
+pub mod noise;
+pub mod normal_map;
+pub mod microfacet;
+pub mod principled;
+pub mod spectrum;
+pub mod thin_film;
+pub mod layered;
+pub mod alpha_mask;
+pub mod subsurface;
+pub mod toon;
+pub mod light;
+pub mod sky;
+pub mod quad;
+pub mod disk;
+pub mod plane;
+pub mod cylinder;
+pub mod cone;
+pub mod polynomial;
+pub mod torus;
+pub mod implicit;
+pub mod sdf;
+pub mod csg;
 fn main() {
     let mut world = HittableList::new();
 
     let ground_material = Arc::new(Lambertian::new(Color::from_xyz(0.5, 0.5, 0.5)));
-    world.add(Arc::new(Sphere::new(&Point3::from_xyz(0.0, -1000.0, 0.0), 1000.0, ground_material.clone())));
+    world.add(Arc::new(Plane::new(&Point3::new(), &Vec3::from_xyz(0.0, 1.0, 0.0), ground_material.clone())));
 
 
     for a in -11..11 {
@@ -75,5 +107,26 @@ fn main() {
     cam.defocus_angle = 0.6;
     cam.focus_dist    = 10.0;
 
-    cam.render(&world);
+    let scene = Scene::from_world(world);
+
+    // The scene picks its integrator, `--integrator <name>` overrides it
+    let integrator = integrator_from_args(scene.integrator.clone().unwrap_or_else(|| Arc::new(PathTracer::new())));
+
+    cam.render(&scene, integrator.as_ref());
+}
+
+fn integrator_from_args(default: Arc<dyn Integrator>) -> Arc<dyn Integrator> {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(i) = args.iter().position(|a| a == "--integrator") else {
+        return default
+    };
+
+    let name = args.get(i + 1).map(|s| s.as_str()).unwrap_or("");
+    match integrator::from_name(name) {
+        Some(integrator) => integrator,
+        None => {
+            eprintln!("Unknown integrator '{}', expected one of: {}", name, INTEGRATOR_NAMES.join(", "));
+            std::process::exit(1);
+        }
+    }
 }
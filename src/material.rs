@@ -1,13 +1,38 @@
 use std::{sync::Arc};
 
-use crate::{color::Color, hittable::HitRecord, rand::random_f64, ray::Ray, vec3::{random_unit_vector, reflect, refract, Vec3}};
+use std::f64::consts::PI;
+
+use crate::{color::Color, hittable::HitRecord, microfacet::local_directions, onb::Onb, rand::random_f64, ray::Ray, spectrum::Dispersion, texture::{SolidColor, Texture}, thin_film::ThinFilm, vec3::{random_cosine_direction, random_unit_vector, reflect, refract, Vec3}};
 
 
 pub trait Material: Send + Sync {
 	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool;
+
+	/// Light given off by the surface towards the incoming ray
+	fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+		Color::new()
+	}
+
+	/// The BSDF times the cosine term for light arriving along `scattered` and leaving back along `r_in`.
+	/// Perfectly specular materials can't be evaluated for an arbitrary direction, so they keep the default of black.
+	fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Color {
+		Color::new()
+	}
+
+	/// Solid angle density with which `scatter` would pick `scattered`.
+	/// Zero means the material is specular (a delta distribution) and can only be sampled, not evaluated.
+	fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+		0.0
+	}
+
+	/// Opacity of the surface at the hit, from 0 to 1. Rays go straight through a surface that isn't
+	/// fully opaque that often, as if it wasn't there, which is how leaves and fences get cut out of quads.
+	fn alpha(&self, _rec: &HitRecord) -> f64 {
+		1.0
+	}
 }
 pub struct Lambertian {
-	albedo: Color,
+	tex: Arc<dyn Texture>,
 }
 
 pub fn blank_material() -> Arc<dyn Material> {
@@ -16,7 +41,11 @@ pub fn blank_material() -> Arc<dyn Material> {
 
 impl Lambertian {
 	pub fn new(albedo: Color) -> Self{
-		Self {albedo}
+		Self::from_texture(Arc::new(SolidColor::new(albedo)))
+	}
+
+	pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+		Self {tex}
 	}
 }
 
@@ -27,19 +56,179 @@ impl Material for Lambertian {
 		// Catch degenerate scatter direction i.e, random vector is opposite direction of normal
 		if scatter_direction.near_zero() {scatter_direction = rec.normal}
 		*scattered = Ray::from_values(&rec.p, &scatter_direction);
-		*attenuation = self.albedo;
+		*attenuation = self.tex.value(rec.u, rec.v, &rec.p);
 		return true;
 	}
+
+	fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		self.tex.value(rec.u, rec.v, &rec.p) * self.scattering_pdf(r_in, rec, scattered)
+	}
+
+	fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+		// normal + random unit vector is cosine distributed around the normal
+		let cos_theta = Vec3::dot(&rec.normal, &Vec3::unit_vector(&scattered.direction()));
+		f64::max(0.0, cos_theta/PI)
+	}
+
+	fn alpha(&self, rec: &HitRecord) -> f64 {
+		self.tex.alpha(rec.u, rec.v, &rec.p)
+	}
+}
+
+/// Diffuse reflection off a surface made of tiny V-shaped grooves (Oren-Nayar), e.g clay, concrete or the moon.
+/// Rough surfaces look flatter than Lambertian ones and brighten towards the light at grazing angles.
+pub struct OrenNayar {
+	tex: Arc<dyn Texture>,
+	// Terms of the qualitative model, from the standard deviation of the grooves' slope angle
+	a: f64,
+	b: f64,
+}
+
+impl OrenNayar {
+	/// `roughness` is the standard deviation of the grooves' slope angle in radians,
+	/// 0 is Lambertian and clay is around 0.5
+	pub fn new(albedo: Color, roughness: f64) -> Self {
+		Self::from_texture(Arc::new(SolidColor::new(albedo)), roughness)
+	}
+
+	pub fn from_texture(tex: Arc<dyn Texture>, roughness: f64) -> Self {
+		let sigma2 = roughness * roughness;
+		Self { tex, a: 1.0 - sigma2/(2.0 * (sigma2 + 0.33)), b: 0.45 * sigma2/(sigma2 + 0.09) }
+	}
+}
+
+impl Material for OrenNayar {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		let uvw = Onb::new(&rec.normal);
+		*scattered = Ray::from_values(&rec.p, &uvw.transform(&random_cosine_direction()));
+
+		let pdf = self.scattering_pdf(r_in, rec, scattered);
+		if pdf <= 0.0 {
+			return false
+		}
+		*attenuation = self.eval(r_in, rec, scattered)/pdf;
+		true
+	}
+
+	fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		let (wo, wi) = local_directions(r_in, rec, scattered);
+		if wo.z() <= 0.0 || wi.z() <= 0.0 {
+			return Color::new()
+		}
+
+		let sin_o = f64::sqrt(f64::max(0.0, 1.0 - wo.z() * wo.z()));
+		let sin_i = f64::sqrt(f64::max(0.0, 1.0 - wi.z() * wi.z()));
+		let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 {
+			f64::max(0.0, (wo.x() * wi.x() + wo.y() * wi.y())/(sin_o * sin_i))
+		} else {
+			0.0
+		};
+		// sin(alpha) * tan(beta), with alpha the larger of the two angles to the normal and beta the smaller
+		let (sin_alpha, tan_beta) = if wi.z() < wo.z() {(sin_i, sin_o/wo.z())} else {(sin_o, sin_i/wi.z())};
+
+		(self.a + self.b * cos_phi * sin_alpha * tan_beta)/PI * wi.z() * self.tex.value(rec.u, rec.v, &rec.p)
+	}
+
+	fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+		let cos_theta = Vec3::dot(&rec.normal, &Vec3::unit_vector(&scattered.direction()));
+		f64::max(0.0, cos_theta/PI)
+	}
+
+	fn alpha(&self, rec: &HitRecord) -> f64 {
+		self.tex.alpha(rec.u, rec.v, &rec.p)
+	}
+}
+
+/// Thin translucent sheets like leaves, paper or lampshades: light is scattered diffusely to both sides,
+/// `reflectance` of it back towards where it came from and `transmittance` through to the other side.
+/// Both sides of the surface look the same, the object is assumed to have no thickness.
+pub struct DiffuseTransmission {
+	reflectance: Arc<dyn Texture>,
+	transmittance: Arc<dyn Texture>,
+}
+
+impl DiffuseTransmission {
+	pub fn new(reflectance: Color, transmittance: Color) -> Self {
+		Self::from_textures(Arc::new(SolidColor::new(reflectance)), Arc::new(SolidColor::new(transmittance)))
+	}
+
+	pub fn from_textures(reflectance: Arc<dyn Texture>, transmittance: Arc<dyn Texture>) -> Self {
+		Self { reflectance, transmittance }
+	}
+
+	/// Chance of sampling the reflected side rather than the transmitted one
+	fn reflect_probability(reflectance: &Color, transmittance: &Color) -> f64 {
+		let r = reflectance.x() + reflectance.y() + reflectance.z();
+		let t = transmittance.x() + transmittance.y() + transmittance.z();
+		if r + t <= 0.0 {0.5} else {r/(r + t)}
+	}
+}
+
+impl Material for DiffuseTransmission {
+	fn scatter(&self, _r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		let reflectance = self.reflectance.value(rec.u, rec.v, &rec.p);
+		let transmittance = self.transmittance.value(rec.u, rec.v, &rec.p);
+		let p_reflect = Self::reflect_probability(&reflectance, &transmittance);
+
+		// The normal faces the incoming ray, so transmitting means scattering around the flipped normal
+		let (side, albedo, p_side) = if random_f64() < p_reflect {
+			(rec.normal, reflectance, p_reflect)
+		} else {
+			(-rec.normal, transmittance, 1.0 - p_reflect)
+		};
+		if p_side <= 0.0 {
+			return false
+		}
+
+		let uvw = Onb::new(&side);
+		*scattered = Ray::from_values(&rec.p, &uvw.transform(&random_cosine_direction()));
+		*attenuation = albedo/p_side;
+		true
+	}
+
+	fn eval(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		let cos_theta = Vec3::dot(&rec.normal, &Vec3::unit_vector(&scattered.direction()));
+		let albedo = if cos_theta >= 0.0 {
+			self.reflectance.value(rec.u, rec.v, &rec.p)
+		} else {
+			self.transmittance.value(rec.u, rec.v, &rec.p)
+		};
+		cos_theta.abs()/PI * albedo
+	}
+
+	fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+		let reflectance = self.reflectance.value(rec.u, rec.v, &rec.p);
+		let transmittance = self.transmittance.value(rec.u, rec.v, &rec.p);
+		let p_reflect = Self::reflect_probability(&reflectance, &transmittance);
+
+		let cos_theta = Vec3::dot(&rec.normal, &Vec3::unit_vector(&scattered.direction()));
+		let p_side = if cos_theta >= 0.0 {p_reflect} else {1.0 - p_reflect};
+		p_side * cos_theta.abs()/PI
+	}
+
+	fn alpha(&self, rec: &HitRecord) -> f64 {
+		self.reflectance.alpha(rec.u, rec.v, &rec.p)
+	}
 }
 
 pub struct Metal {
-	albedo: Color,
-	fuzz: f64
+	tex: Arc<dyn Texture>,
+	fuzz: f64,
+	coating: Option<ThinFilm>
 }
 
 impl Metal {
 	pub fn new(albedo: &Color, fuzz: f64) -> Self {
-		Self { albedo: *albedo, fuzz }
+		Self::from_texture(Arc::new(SolidColor::new(*albedo)), fuzz)
+	}
+
+	pub fn from_texture(tex: Arc<dyn Texture>, fuzz: f64) -> Self {
+		Self { tex, fuzz, coating: None }
+	}
+
+	/// Metal under a thin transparent film, e.g heat tinted steel or an oil slick
+	pub fn coated(albedo: &Color, fuzz: f64, coating: ThinFilm) -> Self {
+		Self { tex: Arc::new(SolidColor::new(*albedo)), fuzz, coating: Some(coating) }
 	}
 }
 
@@ -49,20 +238,52 @@ impl Material for Metal {
 		let reflected = reflect(&r_in.direction(), &rec.normal);
 		let reflected = Vec3::unit_vector(&reflected) + (self.fuzz * random_unit_vector());
 		*scattered = Ray::from_values(&rec.p, &reflected);
-		*attenuation = self.albedo;
+		*attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+		if let Some(coating) = &self.coating {
+			let cos_theta = f64::min(Vec3::dot(&-Vec3::unit_vector(&r_in.direction()), &rec.normal), 1.0);
+			*attenuation = coating.reflectance_over_mirror(rec, cos_theta, attenuation, r_in.wavelength());
+		}
 		return Vec3::dot(&scattered.direction(), &rec.normal) > 0.0;
 	}
+
+	fn alpha(&self, rec: &HitRecord) -> f64 {
+		self.tex.alpha(rec.u, rec.v, &rec.p)
+	}
 }
 
 pub struct Dielectric {
 	// Refractive index in vacuum or air, or the ratio of the material's refractive index over
 	// the refractive index of the enclosing media
-	refraction_index: f64
+	refraction_index: f64,
+	// Beer-Lambert absorption coefficient per unit of distance travelled inside, black for clear glass
+	absorption: Color,
+	// Replaces the refraction index for rays of a known wavelength, in spectral mode
+	dispersion: Option<Dispersion>,
+	// Replaces the Fresnel reflectance of the bare surface
+	coating: Option<ThinFilm>
 }
 
 impl Dielectric {
 	pub fn new(refraction_index: f64) -> Self {
-		Self { refraction_index }
+		Self { refraction_index, absorption: Color::new(), dispersion: None, coating: None }
+	}
+
+	/// Glass whose refraction index depends on the wavelength. Outside of spectral mode
+	/// it behaves like plain glass with the index at 550nm.
+	pub fn dispersive(dispersion: Dispersion) -> Self {
+		Self { refraction_index: dispersion.ior(550.0), absorption: Color::new(), dispersion: Some(dispersion), coating: None }
+	}
+
+	/// Colored glass: light travelling `1/density` units through the interior is filtered down to `tint`,
+	/// so thick parts look darker and more saturated than thin ones
+	pub fn tinted(refraction_index: f64, tint: &Color, density: f64) -> Self {
+		let sigma = |c: f64| -f64::ln(c.clamp(1e-4, 1.0)) * f64::max(0.0, density);
+		Self { refraction_index, absorption: Color::from_xyz(sigma(tint.x()), sigma(tint.y()), sigma(tint.z())), dispersion: None, coating: None }
+	}
+
+	/// Glass with a thin film on its surface. A film on glass with a refraction index of 1 makes a soap bubble.
+	pub fn coated(refraction_index: f64, coating: ThinFilm) -> Self {
+		Self { refraction_index, absorption: Color::new(), dispersion: None, coating: Some(coating) }
 	}
 
 	fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
@@ -76,7 +297,20 @@ impl Dielectric {
 impl Material for Dielectric {
 	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
 		*attenuation = Color::from_xyz(1.0, 1.0, 1.0);
-		let ri = if rec.front_face {1.0/self.refraction_index} else {self.refraction_index};
+		if !rec.front_face {
+			// Hitting the inside of the surface means the ray travelled through the glass to get here
+			let distance = rec.t * r_in.direction().length();
+			*attenuation = Color::from_xyz(
+				f64::exp(-self.absorption.x() * distance),
+				f64::exp(-self.absorption.y() * distance),
+				f64::exp(-self.absorption.z() * distance),
+			);
+		}
+		let refraction_index = match (self.dispersion, r_in.wavelength()) {
+			(Some(dispersion), Some(wavelength)) => dispersion.ior(wavelength),
+			_ => self.refraction_index
+		};
+		let ri = if rec.front_face {1.0/refraction_index} else {refraction_index};
 
 		let unit_direction =  Vec3::unit_vector(&r_in.direction());
 		let cos_theta = f64::min(Vec3::dot(&-unit_direction, &rec.normal), 1.0);
@@ -84,7 +318,22 @@ impl Material for Dielectric {
 
 		let cannot_refract = ri*sin_theta > 1.0;
 
-		let direction = if cannot_refract || Self::reflectance(cos_theta, ri) > random_f64(){
+		let direction = if cannot_refract {
+			reflect(&unit_direction, &rec.normal)
+		} else if let Some(coating) = &self.coating {
+			// The film reflects each color differently, so pick by the average and reweigh
+			let (n_i, n_t) = if rec.front_face {(1.0, refraction_index)} else {(refraction_index, 1.0)};
+			let reflectance = coating.reflectance(rec, cos_theta, n_i, n_t, r_in.wavelength());
+			let p_reflect = (reflectance.x() + reflectance.y() + reflectance.z())/3.0;
+
+			if random_f64() < p_reflect {
+				*attenuation = *attenuation * reflectance/p_reflect;
+				reflect(&unit_direction, &rec.normal)
+			} else {
+				*attenuation = *attenuation * (Color::from_xyz(1.0, 1.0, 1.0) - reflectance)/(1.0 - p_reflect);
+				refract(&unit_direction, &rec.normal, ri)
+			}
+		} else if Self::reflectance(cos_theta, ri) > random_f64() {
 			reflect(&unit_direction, &rec.normal)
 		} else {
 			refract(&unit_direction, &rec.normal, ri)
@@ -93,4 +342,109 @@ impl Material for Dielectric {
 		*scattered = Ray::from_values(&rec.p, &direction);
 		return true;
 	}	
-}
\ No newline at end of file
+}
+
+/// An emissive material, used to turn any hittable into an area light
+pub struct DiffuseLight {
+	emit: Color
+}
+
+impl DiffuseLight {
+	pub fn new(emit: Color) -> Self {
+		Self { emit }
+	}
+}
+
+impl Material for DiffuseLight {
+	fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _attenuation: &mut Color, _scattered: &mut Ray) -> bool {
+		false
+	}
+
+	fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+		// Lights only shine from their front side
+		if !rec.front_face {
+			return Color::new()
+		}
+		self.emit
+	}
+}
+
+/// Phase function for participating media, scatters uniformly in all directions
+pub struct Isotropic {
+	albedo: Color
+}
+
+impl Isotropic {
+	pub fn new(albedo: Color) -> Self {
+		Self { albedo }
+	}
+}
+
+impl Material for Isotropic {
+	fn scatter(&self, _r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		*scattered = Ray::from_values(&rec.p, &random_unit_vector());
+		*attenuation = self.albedo;
+		true
+	}
+
+	// There is no surface inside a volume, so no cosine term either
+	fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		self.albedo * self.scattering_pdf(r_in, rec, scattered)
+	}
+
+	fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+		1.0/(4.0 * PI)
+	}
+}
+
+/// Henyey-Greenstein phase function for participating media. `g` in (-1, 1) controls
+/// whether light mostly scatters backwards (g < 0), evenly (g = 0) or forwards (g > 0), e.g clouds are around 0.8
+pub struct HenyeyGreenstein {
+	albedo: Color,
+	g: f64
+}
+
+impl HenyeyGreenstein {
+	pub fn new(albedo: Color, g: f64) -> Self {
+		Self { albedo, g: g.clamp(-0.99, 0.99) }
+	}
+
+	fn phase(&self, cos_theta: f64) -> f64 {
+		let g = self.g;
+		let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+		(1.0 - g * g)/(4.0 * PI * denom * denom.sqrt())
+	}
+}
+
+impl Material for HenyeyGreenstein {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		let g = self.g;
+		let xi = random_f64();
+
+		// Invert the HG distribution for the angle from the direction of travel
+		let cos_theta = if g.abs() < 1e-3 {
+			1.0 - 2.0 * xi
+		} else {
+			let sq = (1.0 - g * g)/(1.0 - g + 2.0 * g * xi);
+			(1.0 + g * g - sq * sq)/(2.0 * g)
+		};
+		let sin_theta = f64::sqrt(f64::max(0.0, 1.0 - cos_theta * cos_theta));
+		let phi = 2.0 * PI * random_f64();
+
+		let uvw = Onb::new(&r_in.direction());
+		let direction = uvw.transform(&Vec3::from_xyz(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta));
+
+		*scattered = Ray::from_values(&rec.p, &direction);
+		*attenuation = self.albedo;
+		true
+	}
+
+	fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		self.albedo * self.scattering_pdf(r_in, rec, scattered)
+	}
+
+	fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, scattered: &Ray) -> f64 {
+		let cos_theta = Vec3::dot(&Vec3::unit_vector(&r_in.direction()), &Vec3::unit_vector(&scattered.direction()));
+		self.phase(cos_theta)
+	}
+}
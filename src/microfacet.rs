@@ -0,0 +1,407 @@
+use std::{f64::consts::PI, ops::{Add, Div, Mul, Sub}};
+
+use crate::{color::Color, hittable::HitRecord, material::Material, onb::Onb, rand::random_f64, ray::Ray, vec3::Vec3};
+
+// Below this the distribution gets too spiky to evaluate reliably, surfaces are near mirrors anyway
+const MIN_ALPHA: f64 = 1e-3;
+
+/// The Trowbridge-Reitz (GGX) distribution of microfacet normals, in a local frame where the surface normal is +z.
+/// Comes with the Smith shadowing-masking terms and sampling of the normals visible from a direction.
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+	alpha: f64,
+}
+
+impl TrowbridgeReitz {
+	/// `roughness` from 0 (polished) to 1 (completely rough), squared into the distribution's alpha
+	/// so that it changes the look roughly evenly over its range
+	pub fn new(roughness: f64) -> Self {
+		let roughness = roughness.clamp(0.0, 1.0);
+		Self::from_alpha(roughness * roughness)
+	}
+
+	pub fn from_alpha(alpha: f64) -> Self {
+		Self { alpha: f64::max(alpha, MIN_ALPHA) }
+	}
+
+	pub fn alpha(&self) -> f64 {
+		self.alpha
+	}
+
+	/// Density of microfacets facing `wm`, per unit of projected area
+	pub fn d(&self, wm: &Vec3) -> f64 {
+		let cos2 = wm.z() * wm.z();
+		if cos2 <= 0.0 {
+			return 0.0
+		}
+		let tan2 = (1.0 - cos2)/cos2;
+		let a2 = self.alpha * self.alpha;
+		let e = 1.0 + tan2/a2;
+
+		1.0/(PI * a2 * cos2 * cos2 * e * e)
+	}
+
+	/// Smith's auxiliary function, how much microfacet area is hidden per unit of visible area seen from `w`
+	fn lambda(&self, w: &Vec3) -> f64 {
+		let cos2 = w.z() * w.z();
+		if cos2 <= 0.0 {
+			return f64::INFINITY
+		}
+		let tan2 = (1.0 - cos2)/cos2;
+
+		(f64::sqrt(1.0 + self.alpha * self.alpha * tan2) - 1.0)/2.0
+	}
+
+	/// Fraction of microfacets visible from `w`
+	pub fn g1(&self, w: &Vec3) -> f64 {
+		1.0/(1.0 + self.lambda(w))
+	}
+
+	/// Fraction of microfacets visible from both `wo` and `wi`
+	pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+		1.0/(1.0 + self.lambda(wo) + self.lambda(wi))
+	}
+
+	/// Density of the microfacet normals seen from `wo`, which is what `sample_wm` picks from
+	pub fn visible_d(&self, wo: &Vec3, wm: &Vec3) -> f64 {
+		if wo.z() == 0.0 {
+			return 0.0
+		}
+		self.g1(wo)/wo.z().abs() * self.d(wm) * Vec3::dot(wo, wm).abs()
+	}
+
+	/// Picks a microfacet normal visible from `wo`, which must be above the surface (Heitz 2018)
+	pub fn sample_wm(&self, wo: &Vec3) -> Vec3 {
+		// Stretch the view direction so the distribution becomes a hemisphere
+		let vh = Vec3::unit_vector(&Vec3::from_xyz(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()));
+		let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+		let t1 = if lensq > 0.0 {Vec3::from_xyz(-vh.y(), vh.x(), 0.0)/lensq.sqrt()} else {Vec3::from_xyz(1.0, 0.0, 0.0)};
+		let t2 = Vec3::cross(&vh, &t1);
+
+		// Uniform point on a disk, squashed onto the part of the hemisphere seen from vh
+		let r = random_f64().sqrt();
+		let phi = 2.0 * PI * random_f64();
+		let p1 = r * phi.cos();
+		let s = 0.5 * (1.0 + vh.z());
+		let p2 = (1.0 - s) * f64::sqrt(1.0 - p1 * p1) + s * r * phi.sin();
+		let nh = p1 * t1 + p2 * t2 + f64::sqrt(f64::max(0.0, 1.0 - p1 * p1 - p2 * p2)) * vh;
+
+		// and back to the ellipsoid
+		Vec3::unit_vector(&Vec3::from_xyz(self.alpha * nh.x(), self.alpha * nh.y(), f64::max(1e-6, nh.z())))
+	}
+}
+
+/// Mirror direction of `wo` about `n`, both pointing away from the surface
+pub fn reflect(wo: &Vec3, n: &Vec3) -> Vec3 {
+	-*wo + 2.0 * Vec3::dot(wo, n) * *n
+}
+
+/// Direction of `wi` after passing through a surface with normal `n` on its side, `eta` being
+/// the refraction index on the far side over the one on `wi`'s side. None on total internal reflection.
+pub fn refract(wi: &Vec3, n: &Vec3, eta: f64) -> Option<Vec3> {
+	let cos_i = Vec3::dot(n, wi);
+	let sin2_t = f64::max(0.0, 1.0 - cos_i * cos_i)/(eta * eta);
+	if sin2_t >= 1.0 {
+		return None
+	}
+	let cos_t = f64::sqrt(1.0 - sin2_t);
+
+	Some(-*wi/eta + (cos_i/eta - cos_t) * *n)
+}
+
+/// Unpolarized Fresnel reflectance of a dielectric, for light arriving at `cos_i` from the side where
+/// the refraction index on the far side over this one is `eta`
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+	let cos_i = cos_i.clamp(0.0, 1.0);
+	let sin2_t = (1.0 - cos_i * cos_i)/(eta * eta);
+	if sin2_t >= 1.0 {
+		return 1.0
+	}
+	let cos_t = f64::sqrt(1.0 - sin2_t);
+
+	let r_parallel = (eta * cos_i - cos_t)/(eta * cos_i + cos_t);
+	let r_perpendicular = (cos_i - eta * cos_t)/(cos_i + eta * cos_t);
+	(r_parallel * r_parallel + r_perpendicular * r_perpendicular)/2.0
+}
+
+#[derive(Clone, Copy)]
+struct Complex {
+	re: f64,
+	im: f64,
+}
+
+impl Complex {
+	fn new(re: f64, im: f64) -> Self {
+		Self { re, im }
+	}
+
+	fn norm(&self) -> f64 {
+		self.re * self.re + self.im * self.im
+	}
+
+	fn sqrt(&self) -> Self {
+		let n = self.norm().sqrt();
+		if n == 0.0 {
+			return Self::new(0.0, 0.0)
+		}
+		let t1 = f64::sqrt(0.5 * (n + self.re.abs()));
+		let t2 = 0.5 * self.im/t1;
+
+		if self.re >= 0.0 {Self::new(t1, t2)} else {Self::new(t2.abs(), t1.copysign(self.im))}
+	}
+}
+
+impl Add for Complex {
+	type Output = Self;
+	fn add(self, o: Self) -> Self {Self::new(self.re + o.re, self.im + o.im)}
+}
+
+impl Sub for Complex {
+	type Output = Self;
+	fn sub(self, o: Self) -> Self {Self::new(self.re - o.re, self.im - o.im)}
+}
+
+impl Mul for Complex {
+	type Output = Self;
+	fn mul(self, o: Self) -> Self {Self::new(self.re * o.re - self.im * o.im, self.re * o.im + self.im * o.re)}
+}
+
+impl Div for Complex {
+	type Output = Self;
+	fn div(self, o: Self) -> Self {
+		let scale = 1.0/o.norm();
+		Self::new(scale * (self.re * o.re + self.im * o.im), scale * (self.im * o.re - self.re * o.im))
+	}
+}
+
+/// Fresnel reflectance of a conductor with complex refraction index `eta + ik`
+fn fresnel_complex(cos_i: f64, eta: f64, k: f64) -> f64 {
+	let cos_i = cos_i.clamp(0.0, 1.0);
+	let eta = Complex::new(eta, k);
+	let cos_i = Complex::new(cos_i, 0.0);
+	let one = Complex::new(1.0, 0.0);
+
+	let sin2_t = (one - cos_i * cos_i)/(eta * eta);
+	let cos_t = (one - sin2_t).sqrt();
+
+	let r_parallel = (eta * cos_i - cos_t)/(eta * cos_i + cos_t);
+	let r_perpendicular = (cos_i - eta * cos_t)/(cos_i + eta * cos_t);
+	(r_parallel.norm() + r_perpendicular.norm())/2.0
+}
+
+/// Directions leaving and arriving at the hit, in the shading frame around the normal on the ray's side
+pub fn local_directions(r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> (Vec3, Vec3) {
+	let uvw = Onb::new(&rec.normal);
+	(uvw.to_local(&-Vec3::unit_vector(&r_in.direction())), uvw.to_local(&Vec3::unit_vector(&scattered.direction())))
+}
+
+/// A rough metal, reflecting off GGX microfacets with the exact Fresnel term of its complex refraction index.
+/// `eta` and `k` are per color channel, the presets cover common metals.
+pub struct Conductor {
+	eta: Color,
+	k: Color,
+	distribution: TrowbridgeReitz,
+}
+
+impl Conductor {
+	pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+		Self { eta, k, distribution: TrowbridgeReitz::new(roughness) }
+	}
+
+	pub fn gold(roughness: f64) -> Self {
+		Self::new(Color::from_xyz(0.143, 0.375, 1.442), Color::from_xyz(3.983, 2.386, 1.603), roughness)
+	}
+
+	pub fn silver(roughness: f64) -> Self {
+		Self::new(Color::from_xyz(0.155, 0.117, 0.138), Color::from_xyz(4.828, 3.122, 2.147), roughness)
+	}
+
+	pub fn copper(roughness: f64) -> Self {
+		Self::new(Color::from_xyz(0.200, 0.924, 1.102), Color::from_xyz(3.913, 2.453, 2.142), roughness)
+	}
+
+	pub fn aluminium(roughness: f64) -> Self {
+		Self::new(Color::from_xyz(1.657, 0.880, 0.521), Color::from_xyz(9.224, 6.270, 4.837), roughness)
+	}
+
+	pub fn chromium(roughness: f64) -> Self {
+		Self::new(Color::from_xyz(4.370, 2.917, 1.655), Color::from_xyz(5.206, 4.231, 3.755), roughness)
+	}
+
+	pub fn iron(roughness: f64) -> Self {
+		Self::new(Color::from_xyz(2.911, 2.950, 2.585), Color::from_xyz(3.089, 2.932, 2.767), roughness)
+	}
+
+	fn fresnel(&self, cos_i: f64) -> Color {
+		Color::from_xyz(
+			fresnel_complex(cos_i, self.eta.x(), self.k.x()),
+			fresnel_complex(cos_i, self.eta.y(), self.k.y()),
+			fresnel_complex(cos_i, self.eta.z(), self.k.z()),
+		)
+	}
+}
+
+impl Material for Conductor {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		let uvw = Onb::new(&rec.normal);
+		let wo = uvw.to_local(&-Vec3::unit_vector(&r_in.direction()));
+		if wo.z() <= 0.0 {
+			return false
+		}
+
+		let wm = self.distribution.sample_wm(&wo);
+		let wi = reflect(&wo, &wm);
+		if wi.z() <= 0.0 {
+			// reflected into the surface, shadowed by another microfacet
+			return false
+		}
+
+		// The BSDF over the density of sampling it leaves just the Fresnel and shadowing terms
+		*attenuation = self.fresnel(Vec3::dot(&wo, &wm)) * (self.distribution.g(&wo, &wi)/self.distribution.g1(&wo));
+		*scattered = Ray::from_values(&rec.p, &uvw.transform(&wi));
+		true
+	}
+
+	fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		let (wo, wi) = local_directions(r_in, rec, scattered);
+		if wo.z() <= 0.0 || wi.z() <= 0.0 {
+			return Color::new()
+		}
+		let wm = wo + wi;
+		if wm.length_squared() <= 0.0 {
+			return Color::new()
+		}
+		let wm = Vec3::unit_vector(&wm);
+
+		// D F G/(4 cos_o cos_i), times cos_i
+		self.fresnel(Vec3::dot(&wo, &wm)) * (self.distribution.d(&wm) * self.distribution.g(&wo, &wi)/(4.0 * wo.z()))
+	}
+
+	fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+		let (wo, wi) = local_directions(r_in, rec, scattered);
+		if wo.z() <= 0.0 || wi.z() <= 0.0 {
+			return 0.0
+		}
+		let wm = wo + wi;
+		if wm.length_squared() <= 0.0 {
+			return 0.0
+		}
+		let wm = Vec3::unit_vector(&wm);
+
+		self.distribution.visible_d(&wo, &wm)/(4.0 * Vec3::dot(&wo, &wm).abs())
+	}
+}
+
+/// Frosted glass: reflects and refracts through GGX microfacets, each picked by its Fresnel term
+pub struct RoughDielectric {
+	refraction_index: f64,
+	distribution: TrowbridgeReitz,
+}
+
+impl RoughDielectric {
+	pub fn new(refraction_index: f64, roughness: f64) -> Self {
+		Self { refraction_index, distribution: TrowbridgeReitz::new(roughness) }
+	}
+
+	// Refraction index on the far side of the surface over the one on the ray's side
+	fn eta(&self, rec: &HitRecord) -> f64 {
+		if rec.front_face {self.refraction_index} else {1.0/self.refraction_index}
+	}
+
+	/// The microfacet normal that turns `wo` into `wi`, facing up, and the relative refraction index it was found with.
+	/// None for configurations no microfacet can produce.
+	fn half_vector(&self, wo: &Vec3, wi: &Vec3, eta: f64) -> Option<(Vec3, f64)> {
+		if wo.z() <= 0.0 || wi.z() == 0.0 {
+			return None
+		}
+
+		let eta_p = if wi.z() > 0.0 {1.0} else {eta};
+		let wm = eta_p * *wi + *wo;
+		if wm.length_squared() <= 0.0 {
+			return None
+		}
+		let wm = Vec3::unit_vector(&wm);
+		let wm = if wm.z() < 0.0 {-wm} else {wm};
+
+		// Both directions must be on the front of the microfacet
+		if Vec3::dot(&wm, wi) * wi.z() < 0.0 || Vec3::dot(&wm, wo) < 0.0 {
+			return None
+		}
+		Some((wm, eta_p))
+	}
+}
+
+impl Material for RoughDielectric {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		let eta = self.eta(rec);
+		let uvw = Onb::new(&rec.normal);
+		let wo = uvw.to_local(&-Vec3::unit_vector(&r_in.direction()));
+		if wo.z() <= 0.0 {
+			return false
+		}
+
+		let wm = self.distribution.sample_wm(&wo);
+		let fresnel = fresnel_dielectric(Vec3::dot(&wo, &wm), eta);
+
+		// Picking reflection or refraction by the Fresnel term cancels it out of the weight
+		let wi = if random_f64() < fresnel {
+			let wi = reflect(&wo, &wm);
+			if wi.z() <= 0.0 {
+				return false
+			}
+			wi
+		} else {
+			let Some(wi) = refract(&wo, &wm, eta) else {
+				return false
+			};
+			if wi.z() >= 0.0 {
+				return false
+			}
+			wi
+		};
+
+		*attenuation = Color::from_xyz(1.0, 1.0, 1.0) * (self.distribution.g(&wo, &wi)/self.distribution.g1(&wo));
+		*scattered = Ray::from_values(&rec.p, &uvw.transform(&wi));
+		true
+	}
+
+	fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		let eta = self.eta(rec);
+		let (wo, wi) = local_directions(r_in, rec, scattered);
+		let Some((wm, eta_p)) = self.half_vector(&wo, &wi, eta) else {
+			return Color::new()
+		};
+
+		let fresnel = fresnel_dielectric(Vec3::dot(&wo, &wm), eta);
+		let d = self.distribution.d(&wm);
+		let g = self.distribution.g(&wo, &wi);
+
+		// All of these are the BSDF times cos_i, which cancels against the one in its denominator
+		let f = if wi.z() > 0.0 {
+			d * g * fresnel/(4.0 * wo.z())
+		} else {
+			let denom = Vec3::dot(&wi, &wm) + Vec3::dot(&wo, &wm)/eta_p;
+			d * g * (1.0 - fresnel) * (Vec3::dot(&wi, &wm) * Vec3::dot(&wo, &wm)).abs()/(wo.z() * denom * denom)
+		};
+		Color::from_xyz(f, f, f)
+	}
+
+	fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+		let eta = self.eta(rec);
+		let (wo, wi) = local_directions(r_in, rec, scattered);
+		let Some((wm, eta_p)) = self.half_vector(&wo, &wi, eta) else {
+			return 0.0
+		};
+
+		let fresnel = fresnel_dielectric(Vec3::dot(&wo, &wm), eta);
+		let visible = self.distribution.visible_d(&wo, &wm);
+
+		if wi.z() > 0.0 {
+			fresnel * visible/(4.0 * Vec3::dot(&wo, &wm).abs())
+		} else {
+			// Change of variables from the microfacet normal to the refracted direction
+			let denom = Vec3::dot(&wi, &wm) + Vec3::dot(&wo, &wm)/eta_p;
+			(1.0 - fresnel) * visible * Vec3::dot(&wi, &wm).abs()/(denom * denom)
+		}
+	}
+}
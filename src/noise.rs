@@ -0,0 +1,150 @@
+use crate::{rand::Rand, vec3::{Point3, Vec3}};
+
+const POINT_COUNT: usize = 256;
+
+/// Random permutations of 0..POINT_COUNT, one per axis, hashing lattice points into the noise tables
+struct Permutations {
+	x: [usize; POINT_COUNT],
+	y: [usize; POINT_COUNT],
+	z: [usize; POINT_COUNT],
+}
+
+impl Permutations {
+	fn new(rng: &mut Rand) -> Self {
+		let mut generate = || {
+			let mut p: [usize; POINT_COUNT] = std::array::from_fn(|i| i);
+			rng.shuffle(&mut p);
+			p
+		};
+
+		Self { x: generate(), y: generate(), z: generate() }
+	}
+
+	fn hash(&self, i: i64, j: i64, k: i64) -> usize {
+		let mask = POINT_COUNT as i64 - 1;
+		self.x[(i & mask) as usize] ^ self.y[(j & mask) as usize] ^ self.z[(k & mask) as usize]
+	}
+}
+
+/// Gradient noise on the integer lattice, roughly in [-1, 1].
+/// The same seed always gives the same noise, independent of the thread's random stream.
+pub struct Perlin {
+	gradients: [Vec3; POINT_COUNT],
+	perm: Permutations,
+}
+
+impl Perlin {
+	pub fn new(seed: u32) -> Self {
+		let mut rng = Rand::new(seed);
+		let gradients = std::array::from_fn(|_| loop {
+			let p = Vec3::from_xyz(rng.random_f64_range(-1.0, 1.0), rng.random_f64_range(-1.0, 1.0), rng.random_f64_range(-1.0, 1.0));
+			let lensq = p.length_squared();
+			if 1e-160 < lensq && lensq <= 1.0 {
+				break p/lensq.sqrt()
+			}
+		});
+
+		Self { gradients, perm: Permutations::new(&mut rng) }
+	}
+
+	pub fn noise(&self, p: &Point3) -> f64 {
+		let (fx, fy, fz) = (p.x().floor(), p.y().floor(), p.z().floor());
+		let (u, v, w) = (p.x() - fx, p.y() - fy, p.z() - fz);
+		let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+		// Hermite smoothing hides the lattice
+		let (uu, vv, ww) = (u*u*(3.0 - 2.0*u), v*v*(3.0 - 2.0*v), w*w*(3.0 - 2.0*w));
+
+		let mut accum = 0.0;
+		for di in 0..2 {
+			for dj in 0..2 {
+				for dk in 0..2 {
+					let gradient = self.gradients[self.perm.hash(i + di, j + dj, k + dk)];
+					let (a, b, c) = (di as f64, dj as f64, dk as f64);
+					let weight = Vec3::from_xyz(u - a, v - b, w - c);
+
+					accum += (a*uu + (1.0 - a)*(1.0 - uu))
+						* (b*vv + (1.0 - b)*(1.0 - vv))
+						* (c*ww + (1.0 - c)*(1.0 - ww))
+						* Vec3::dot(&gradient, &weight);
+				}
+			}
+		}
+
+		accum
+	}
+
+	/// Fractal Brownian motion: `octaves` layers of noise, each `lacunarity` times finer and `gain` times weaker
+	pub fn fbm(&self, p: &Point3, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+		let mut accum = 0.0;
+		let mut p = *p;
+		let mut weight = 1.0;
+
+		for _ in 0..octaves {
+			accum += weight * self.noise(&p);
+			weight *= gain;
+			p = lacunarity * p;
+		}
+
+		accum
+	}
+
+	/// Sum of the absolute value of `depth` octaves of noise, giving a billowy, always positive pattern
+	pub fn turbulence(&self, p: &Point3, depth: u32) -> f64 {
+		let mut accum = 0.0;
+		let mut p = *p;
+		let mut weight = 1.0;
+
+		for _ in 0..depth {
+			accum += weight * self.noise(&p).abs();
+			weight *= 0.5;
+			p = 2.0 * p;
+		}
+
+		accum
+	}
+}
+
+/// Cellular noise: every lattice cell holds one feature point, and the noise at a point
+/// is its distance to the closest (F1) and second closest (F2) of them
+pub struct Worley {
+	features: [Vec3; POINT_COUNT],
+	perm: Permutations,
+}
+
+impl Worley {
+	pub fn new(seed: u32) -> Self {
+		let mut rng = Rand::new(seed);
+		let features = std::array::from_fn(|_| Vec3::from_xyz(rng.random_f64(), rng.random_f64(), rng.random_f64()));
+
+		Self { features, perm: Permutations::new(&mut rng) }
+	}
+
+	/// Distances to the closest and second closest feature points
+	pub fn distances(&self, p: &Point3) -> (f64, f64) {
+		let (fx, fy, fz) = (p.x().floor(), p.y().floor(), p.z().floor());
+		let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+		let mut f1 = f64::INFINITY;
+		let mut f2 = f64::INFINITY;
+		// A feature point is inside its cell, so the two closest are always within the neighbouring cells
+		for di in -1..=1 {
+			for dj in -1..=1 {
+				for dk in -1..=1 {
+					let cell = Vec3::from_xyz(fx + di as f64, fy + dj as f64, fz + dk as f64);
+					let feature = cell + self.features[self.perm.hash(i + di, j + dj, k + dk)];
+					let d = (feature - *p).length();
+
+					if d < f1 {
+						f2 = f1;
+						f1 = d;
+					} else if d < f2 {
+						f2 = d;
+					}
+				}
+			}
+		}
+
+		(f1, f2)
+	}
+}
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use crate::{color::Color, hittable::{HitRecord, Hittable, Span}, interval::Interval, ray::Ray, texture::Texture, vec3::{Point3, Vec3}};
+
+// Step in texture coordinates for the finite differences of a bump map
+const BUMP_DELTA: f64 = 0.0005;
+
+/// The surface normal facing away from the object, whichever side the ray came from
+fn outward_normal(rec: &HitRecord) -> Vec3 {
+	if rec.front_face {rec.normal} else {-rec.normal}
+}
+
+/// Replaces the normal with a new outward facing one, keeping it on the side the ray came from
+fn set_shading_normal(rec: &mut HitRecord, outward: &Vec3) {
+	rec.normal = if rec.front_face {*outward} else {-*outward};
+}
+
+/// Wraps an object, bending its normals with a tangent space normal map: the texture's red, green and blue
+/// from 0 to 1 are the normal's tangent, bitangent and normal components from -1 to 1.
+/// Only the shading changes, rays still hit the same surface.
+///
+/// Normal maps hold data, so load them with `Image::load_data` rather than `Image::load`.
+pub struct NormalMapped {
+	object: Arc<dyn Hittable>,
+	map: Arc<dyn Texture>,
+	// scales the sideways part of the mapped normals, 0 leaves the surface flat
+	strength: f64,
+}
+
+impl NormalMapped {
+	pub fn new(object: Arc<dyn Hittable>, map: Arc<dyn Texture>, strength: f64) -> Self {
+		Self { object, map, strength }
+	}
+
+	/// Bends the normal of a hit on the wrapped object
+	fn map_normal(&self, rec: &mut HitRecord) {
+		let n = outward_normal(rec);
+		let t = rec.tangent - Vec3::dot(&rec.tangent, &n) * n;
+		if t.length_squared() <= 0.0 {
+			// no tangent space here, nothing to map
+			return
+		}
+		let t = Vec3::unit_vector(&t);
+		let mut b = Vec3::cross(&n, &t);
+		if Vec3::dot(&b, &rec.bitangent) < 0.0 {
+			b = -b;
+		}
+
+		let m = 2.0 * self.map.value(rec.u, rec.v, &rec.p) - Color::from_xyz(1.0, 1.0, 1.0);
+		let mapped = self.strength * (m.x() * t + m.y() * b) + m.z() * n;
+		if mapped.length_squared() > 0.0 {
+			set_shading_normal(rec, &Vec3::unit_vector(&mapped));
+		}
+	}
+}
+
+impl Hittable for NormalMapped {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		if !self.object.hit(r, ray_t, rec) {
+			return false
+		}
+
+		self.map_normal(rec);
+		true
+	}
+
+	fn spans(&self, r: &Ray) -> Vec<Span> {
+		let mut spans = self.object.spans(r);
+		for span in &mut spans {
+			self.map_normal(&mut span.enter);
+			self.map_normal(&mut span.exit);
+		}
+		spans
+	}
+
+	fn hit_solid(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		self.object.hit_solid(r, ray_t, rec)
+	}
+
+	fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+		self.object.transmittance(r, ray_t)
+	}
+
+	fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+		self.object.pdf_value(origin, direction)
+	}
+
+	fn random(&self, origin: &Point3) -> Vec3 {
+		self.object.random(origin)
+	}
+
+	fn sample_surface(&self, rec: &mut HitRecord) -> f64 {
+		self.object.sample_surface(rec)
+	}
+
+	fn surface_pdf(&self, point: &Point3) -> f64 {
+		self.object.surface_pdf(point)
+	}
+}
+
+/// Wraps an object, bending its normals as if the surface were displaced along them by a height map,
+/// the average of the texture's channels times `scale` in world units.
+/// Only the shading changes, rays still hit the same surface.
+pub struct BumpMapped {
+	object: Arc<dyn Hittable>,
+	height: Arc<dyn Texture>,
+	scale: f64,
+}
+
+impl BumpMapped {
+	pub fn new(object: Arc<dyn Hittable>, height: Arc<dyn Texture>, scale: f64) -> Self {
+		Self { object, height, scale }
+	}
+
+	fn height(&self, u: f64, v: f64, p: &Point3) -> f64 {
+		let c = self.height.value(u, v, p);
+		self.scale * (c.x() + c.y() + c.z())/3.0
+	}
+
+	/// Bends the normal of a hit on the wrapped object
+	fn bump_normal(&self, rec: &mut HitRecord) {
+		let n = outward_normal(rec);
+		let (dpdu, dpdv) = (rec.tangent, rec.bitangent);
+
+		// Finite differences of the height along u and v, moving the point along with the texture coordinates
+		// so solid textures get bumped as well
+		let h = self.height(rec.u, rec.v, &rec.p);
+		let dhdu = (self.height(rec.u + BUMP_DELTA, rec.v, &(rec.p + BUMP_DELTA * dpdu)) - h)/BUMP_DELTA;
+		let dhdv = (self.height(rec.u, rec.v + BUMP_DELTA, &(rec.p + BUMP_DELTA * dpdv)) - h)/BUMP_DELTA;
+
+		// The displaced surface p + h*n has these derivatives, ignoring how n itself changes
+		let bumped = Vec3::cross(&(dpdu + dhdu * n), &(dpdv + dhdv * n));
+		if bumped.length_squared() <= 0.0 {
+			return
+		}
+		let bumped = Vec3::unit_vector(&bumped);
+		set_shading_normal(rec, &if Vec3::dot(&bumped, &n) < 0.0 {-bumped} else {bumped});
+	}
+}
+
+impl Hittable for BumpMapped {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		if !self.object.hit(r, ray_t, rec) {
+			return false
+		}
+
+		self.bump_normal(rec);
+		true
+	}
+
+	fn spans(&self, r: &Ray) -> Vec<Span> {
+		let mut spans = self.object.spans(r);
+		for span in &mut spans {
+			self.bump_normal(&mut span.enter);
+			self.bump_normal(&mut span.exit);
+		}
+		spans
+	}
+
+	fn hit_solid(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		self.object.hit_solid(r, ray_t, rec)
+	}
+
+	fn transmittance(&self, r: &Ray, ray_t: &Interval) -> Color {
+		self.object.transmittance(r, ray_t)
+	}
+
+	fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+		self.object.pdf_value(origin, direction)
+	}
+
+	fn random(&self, origin: &Point3) -> Vec3 {
+		self.object.random(origin)
+	}
+
+	fn sample_surface(&self, rec: &mut HitRecord) -> f64 {
+		self.object.sample_surface(rec)
+	}
+
+	fn surface_pdf(&self, point: &Point3) -> f64 {
+		self.object.surface_pdf(point)
+	}
+}
@@ -0,0 +1,33 @@
+use crate::vec3::Vec3;
+
+/// An orthonormal basis built around a single direction, used to move sampled
+/// directions from a local frame (where w is "up") into world space.
+pub struct Onb {
+	axis: [Vec3; 3]
+}
+
+impl Onb {
+	pub fn new(n: &Vec3) -> Self {
+		let w = Vec3::unit_vector(n);
+		// any vector not parallel to w will do for building the other two axes
+		let a = if w.x().abs() > 0.9 {Vec3::from_xyz(0.0, 1.0, 0.0)} else {Vec3::from_xyz(1.0, 0.0, 0.0)};
+		let v = Vec3::unit_vector(&Vec3::cross(&w, &a));
+		let u = Vec3::cross(&w, &v);
+
+		Self { axis: [u, v, w] }
+	}
+
+	pub fn u(&self) -> Vec3 {self.axis[0]}
+	pub fn v(&self) -> Vec3 {self.axis[1]}
+	pub fn w(&self) -> Vec3 {self.axis[2]}
+
+	/// Transform from basis coordinates to world space
+	pub fn transform(&self, v: &Vec3) -> Vec3 {
+		(v.x() * self.axis[0]) + (v.y() * self.axis[1]) + (v.z() * self.axis[2])
+	}
+
+	/// Transform from world space to basis coordinates, the inverse of `transform`
+	pub fn to_local(&self, v: &Vec3) -> Vec3 {
+		Vec3::from_xyz(Vec3::dot(v, &self.axis[0]), Vec3::dot(v, &self.axis[1]), Vec3::dot(v, &self.axis[2]))
+	}
+}
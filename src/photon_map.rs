@@ -0,0 +1,293 @@
+use std::{cmp::Ordering, collections::BinaryHeap, f64::consts::PI, sync::RwLock};
+use rayon::prelude::*;
+
+use crate::{camera::Camera, color::Color, hittable::{HitRecord, Hittable}, integrator::{escaped_radiance, sample_delta_lights, sample_lights, sample_sun, Integrator, T_MIN}, interval::Interval, onb::Onb, rand::random_f64, ray::Ray, scene::Scene, vec3::{random_cosine_direction, Point3, Vec3}};
+
+/// A packet of light deposited on a diffuse surface
+#[derive(Clone, Copy)]
+pub struct Photon {
+	pub p: Point3,
+	// Direction the photon was travelling in when it landed
+	pub direction: Vec3,
+	pub power: Color,
+}
+
+/// Photons stored as an implicit, balanced kd-tree: the middle photon of every range splits
+/// the rest of the range along `axes[middle]`.
+pub struct PhotonMap {
+	photons: Vec<Photon>,
+	axes: Vec<usize>,
+}
+
+/// A photon found by `PhotonMap::nearest`, ordered by distance so the farthest sits on top of the heap
+pub struct Neighbour {
+	pub distance_squared: f64,
+	pub index: usize,
+}
+
+impl PartialEq for Neighbour {
+	fn eq(&self, other: &Self) -> bool {
+		self.distance_squared == other.distance_squared
+	}
+}
+
+impl Eq for Neighbour {}
+
+impl PartialOrd for Neighbour {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Neighbour {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.distance_squared.total_cmp(&other.distance_squared)
+	}
+}
+
+impl PhotonMap {
+	pub fn new(photons: Vec<Photon>) -> Self {
+		let mut photons = photons;
+		let mut axes = vec![0; photons.len()];
+		Self::build(&mut photons, &mut axes);
+
+		Self { photons, axes }
+	}
+
+	pub fn len(&self) -> usize {
+		self.photons.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.photons.is_empty()
+	}
+
+	pub fn photon(&self, index: usize) -> &Photon {
+		&self.photons[index]
+	}
+
+	fn build(photons: &mut [Photon], axes: &mut [usize]) {
+		if photons.len() <= 1 {
+			return
+		}
+
+		// Split along the axis the photons are most spread out on
+		let mut min = [f64::INFINITY; 3];
+		let mut max = [f64::NEG_INFINITY; 3];
+		for photon in photons.iter() {
+			for axis in 0..3 {
+				min[axis] = min[axis].min(photon.p[axis]);
+				max[axis] = max[axis].max(photon.p[axis]);
+			}
+		}
+		let axis = (0..3).max_by(|&a, &b| (max[a] - min[a]).total_cmp(&(max[b] - min[b]))).unwrap();
+
+		let middle = photons.len()/2;
+		photons.select_nth_unstable_by(middle, |a, b| a.p[axis].total_cmp(&b.p[axis]));
+		axes[middle] = axis;
+
+		let (left, right) = photons.split_at_mut(middle);
+		let (left_axes, right_axes) = axes.split_at_mut(middle);
+		Self::build(left, left_axes);
+		Self::build(&mut right[1..], &mut right_axes[1..]);
+	}
+
+	/// Returns up to `k` photons closest to `p` within `max_distance_squared`, in no particular order
+	pub fn nearest(&self, p: &Point3, k: usize, max_distance_squared: f64) -> Vec<Neighbour> {
+		let mut heap = BinaryHeap::with_capacity(k + 1);
+		let mut max_distance_squared = max_distance_squared;
+		self.search(0, self.photons.len(), p, k, &mut max_distance_squared, &mut heap);
+
+		heap.into_vec()
+	}
+
+	fn search(&self, lo: usize, hi: usize, p: &Point3, k: usize, max_distance_squared: &mut f64, heap: &mut BinaryHeap<Neighbour>) {
+		if lo >= hi {
+			return
+		}
+
+		let middle = lo + (hi - lo)/2;
+		let photon = &self.photons[middle];
+		let axis = self.axes[middle];
+		let delta = p[axis] - photon.p[axis];
+
+		// Visit the side of the split the point is on first, it most likely holds the closest photons
+		let (near, far) = if delta < 0.0 {((lo, middle), (middle + 1, hi))} else {((middle + 1, hi), (lo, middle))};
+		self.search(near.0, near.1, p, k, max_distance_squared, heap);
+
+		let distance_squared = (photon.p - *p).length_squared();
+		if distance_squared < *max_distance_squared {
+			heap.push(Neighbour { distance_squared, index: middle });
+			if heap.len() > k {
+				heap.pop();
+			}
+			if heap.len() == k {
+				// the search area shrinks to the farthest photon kept so far
+				*max_distance_squared = heap.peek().unwrap().distance_squared;
+			}
+		}
+
+		if delta * delta < *max_distance_squared {
+			self.search(far.0, far.1, p, k, max_distance_squared, heap);
+		}
+	}
+}
+
+/// Two-pass photon mapping. Before rendering, photons are shot from `Scene::lights` into a global map
+/// (light that bounced off at least one diffuse surface) and a caustic map (light focused by specular
+/// surfaces only, e.g through glass). Camera rays are followed through specular surfaces and at the first
+/// diffuse hit direct light is sampled as usual, while caustics and indirect light come from the maps.
+///
+/// Light from the sky is only picked up directly, it is not shot as photons.
+pub struct PhotonMapper {
+	global_photons: usize,
+	caustic_photons: usize,
+	// photons gathered per radiance estimate and the farthest they may be
+	neighbours: usize,
+	max_radius: f64,
+	maps: RwLock<(PhotonMap, PhotonMap)>,
+}
+
+impl PhotonMapper {
+	pub fn new(global_photons: usize, caustic_photons: usize, neighbours: usize, max_radius: f64) -> Self {
+		Self {
+			global_photons,
+			caustic_photons,
+			neighbours: neighbours.max(1),
+			max_radius,
+			maps: RwLock::new((PhotonMap::new(Vec::new()), PhotonMap::new(Vec::new()))),
+		}
+	}
+
+	/// Density estimate of the light reflected back along `r` at `rec`, from the photons around it
+	fn estimate(&self, map: &PhotonMap, r: &Ray, rec: &HitRecord) -> Color {
+		let neighbours = map.nearest(&rec.p, self.neighbours, self.max_radius * self.max_radius);
+		let Some(radius_squared) = neighbours.iter().map(|n| n.distance_squared).max_by(f64::total_cmp) else {
+			return Color::new()
+		};
+		if radius_squared <= 0.0 {
+			return Color::new()
+		}
+
+		let mut sum = Color::new();
+		for neighbour in &neighbours {
+			let photon = map.photon(neighbour.index);
+			let to_light = Ray::from_values(&rec.p, &-photon.direction);
+			// Photons arriving from behind the surface belong to whatever is on the other side
+			let cos_theta = Vec3::dot(&rec.normal, &to_light.direction());
+			if cos_theta <= 0.0 {
+				continue
+			}
+			sum += rec.mat.eval(r, rec, &to_light)/cos_theta * photon.power;
+		}
+
+		sum/(PI * radius_squared)
+	}
+}
+
+/// Shoots `count` photons from the scene's lights and keeps the ones landing on diffuse surfaces
+/// after either only specular bounces (`caustics`) or at least one diffuse bounce
+fn trace_photons(scene: &Scene, count: usize, max_depth: i32, caustics: bool) -> Vec<Photon> {
+	(0..count).into_par_iter().flat_map_iter(|_| {
+		let mut photons = Vec::new();
+		trace_photon(scene, count, max_depth, caustics, &mut photons);
+		photons
+	}).collect()
+}
+
+fn trace_photon(scene: &Scene, count: usize, max_depth: i32, caustics: bool, photons: &mut Vec<Photon>) {
+	let mut rec = HitRecord::new();
+	let pdf_pos = scene.lights.sample_surface(&mut rec);
+	if pdf_pos <= 0.0 {
+		return
+	}
+
+	// Diffuse emitters send light out in a cosine distribution, which cancels against the cosine in the flux
+	let direction = Onb::new(&rec.normal).transform(&random_cosine_direction());
+	let le = rec.mat.emitted(&Ray::from_values(&(rec.p + direction), &-direction), &rec);
+	let mut power = le * PI/(pdf_pos * count as f64);
+	let mut r = Ray::from_values(&rec.p, &direction);
+
+	let mut diffuse_bounces = 0;
+	let mut specular_bounces = 0;
+
+	for _ in 0..max_depth {
+		let mut rec = HitRecord::new();
+		if !scene.world.hit(&r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			return
+		}
+
+		let mut scattered = Ray::new();
+		let mut attenuation = Color::new();
+		if !rec.mat.scatter(&r, &rec, &mut attenuation, &mut scattered) {
+			return
+		}
+
+		if rec.mat.scattering_pdf(&r, &rec, &scattered) > 0.0 {
+			let store = if caustics {diffuse_bounces == 0 && specular_bounces > 0} else {diffuse_bounces > 0};
+			if store {
+				photons.push(Photon { p: rec.p, direction: Vec3::unit_vector(&r.direction()), power });
+			}
+			if caustics {
+				// a caustic path ends at its first diffuse surface
+				return
+			}
+			diffuse_bounces += 1;
+		} else {
+			specular_bounces += 1;
+		}
+
+		// Russian roulette keeps photon powers roughly equal instead of letting them fade
+		let survival = f64::min(1.0, f64::max(attenuation.x(), f64::max(attenuation.y(), attenuation.z())));
+		if survival <= 0.0 || random_f64() >= survival {
+			return
+		}
+		power = power * attenuation/survival;
+		r = scattered;
+	}
+}
+
+impl Integrator for PhotonMapper {
+	fn preprocess(&self, scene: &Scene, cam: &Camera) {
+		let global = PhotonMap::new(trace_photons(scene, self.global_photons, cam.max_depth, false));
+		let caustic = PhotonMap::new(trace_photons(scene, self.caustic_photons, cam.max_depth, true));
+		eprintln!("Stored {} global and {} caustic photons", global.len(), caustic.len());
+
+		*self.maps.write().unwrap() = (global, caustic);
+	}
+
+	fn ray_color(&self, r: &Ray, depth: i32, scene: &Scene) -> Color {
+		if depth <= 0 {
+			return Color::new()
+		}
+
+		let mut rec = HitRecord::new();
+
+		if !scene.world.hit(r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			return scene.background(r)
+		}
+
+		let emitted = rec.mat.emitted(r, &rec);
+		let mut scattered = Ray::new();
+		let mut attenuation = Color::new();
+
+		if !rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
+			return emitted
+		}
+
+		let pdf = rec.mat.scattering_pdf(r, &rec, &scattered);
+		if pdf <= 0.0 {
+			return emitted + attenuation * self.ray_color(&scattered, depth-1, scene)
+		}
+
+		let mut sky = Color::new();
+		let mut sky_rec = HitRecord::new();
+		if !scene.world.hit(&scattered, &Interval::from_values(T_MIN, f64::INFINITY), &mut sky_rec) {
+			sky = attenuation * escaped_radiance(&scattered, pdf, scene);
+		}
+
+		let maps = self.maps.read().unwrap();
+		let (global, caustic) = &*maps;
+		emitted + sample_lights(r, &rec, scene) + sample_delta_lights(r, &rec, scene) + sample_sun(r, &rec, scene) + sky + self.estimate(caustic, r, &rec) + self.estimate(global, r, &rec)
+	}
+}
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use crate::{hittable::{HitRecord, Hittable}, interval::Interval, material::Material, onb::Onb, ray::Ray, vec3::{Point3, Vec3}};
+
+// Parallel ray epsilon
+const E: f64 = 1e-8;
+
+/// An infinite plane through `point` facing along `normal`, e.g a ground or a backdrop.
+/// Texture coordinates are distances along two axes in the plane, so textures tile across it in scene units.
+///
+/// It has no finite area, so unlike `Quad` and `Disk` it can't be sampled as a light.
+pub struct Plane {
+	point: Point3,
+	mat: Arc<dyn Material>,
+	// Unit normal and two unit axes in the plane, with tangent × bitangent = normal
+	normal: Vec3,
+	tangent: Vec3,
+	bitangent: Vec3,
+}
+
+impl Plane {
+	pub fn new(point: &Point3, normal: &Vec3, mat: Arc<dyn Material>) -> Self {
+		let normal = Vec3::unit_vector(normal);
+		let tangent = Onb::new(&normal).u();
+		Self { point: *point, mat, normal, tangent, bitangent: Vec3::cross(&normal, &tangent) }
+	}
+}
+
+impl Hittable for Plane {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		let denom = Vec3::dot(&self.normal, &r.direction());
+		if denom.abs() < E {
+			return false
+		}
+
+		let t = Vec3::dot(&self.normal, &(self.point - r.origin()))/denom;
+		if !ray_t.surrounds(t) {
+			return false
+		}
+
+		rec.t = t;
+		rec.p = r.at(t);
+		let local = rec.p - self.point;
+		(rec.u, rec.v) = (Vec3::dot(&local, &self.tangent), Vec3::dot(&local, &self.bitangent));
+		(rec.tangent, rec.bitangent) = (self.tangent, self.bitangent);
+		rec.set_face_normal(r, &self.normal);
+		rec.mat = self.mat.clone();
+
+		!rec.is_cut_out(r)
+	}
+}
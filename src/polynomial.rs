@@ -0,0 +1,158 @@
+// Polynomials in one variable, as coefficients from the constant term up (c[0] + c[1]x + c[2]x² + ...)
+
+/// Value of the polynomial at `x`
+pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+	coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+
+pub fn derivative(coefficients: &[f64]) -> Vec<f64> {
+	coefficients.iter().enumerate().skip(1).map(|(i, c)| i as f64 * c).collect()
+}
+
+pub fn multiply(a: &[f64], b: &[f64]) -> Vec<f64> {
+	if a.is_empty() || b.is_empty() {
+		return Vec::new()
+	}
+
+	let mut product = vec![0.0; a.len() + b.len() - 1];
+	for (i, x) in a.iter().enumerate() {
+		for (j, y) in b.iter().enumerate() {
+			product[i + j] += x * y;
+		}
+	}
+	product
+}
+
+/// The real roots within [`min`, `max`], in ascending order.
+///
+/// Rather than a closed form, which loses most of its digits for quartics like a torus seen edge on,
+/// the roots are isolated by the roots of the derivative, found the same way. Between two of those the polynomial
+/// only goes up or down, so it has a root there exactly when the ends differ in sign, and safeguarded Newton steps
+/// close in on it. Roots where the polynomial only touches zero without crossing are missed, for a ray that's
+/// a graze it can do without.
+pub fn roots_in(coefficients: &[f64], min: f64, max: f64) -> Vec<f64> {
+	let degree = match coefficients.iter().rposition(|c| *c != 0.0) {
+		Some(degree) => degree,
+		None => return Vec::new(),
+	};
+	let coefficients = &coefficients[..=degree];
+
+	match degree {
+		0 => return Vec::new(),
+		1 => {
+			let root = -coefficients[0]/coefficients[1];
+			return if min <= root && root <= max {vec![root]} else {Vec::new()}
+		}
+		_ => (),
+	}
+
+	let slope = derivative(coefficients);
+	let mut ends = vec![min];
+	ends.extend(roots_in(&slope, min, max));
+	ends.push(max);
+
+	let mut roots: Vec<f64> = Vec::new();
+	for pair in ends.windows(2) {
+		let (a, b) = (pair[0], pair[1]);
+		let (fa, fb) = (evaluate(coefficients, a), evaluate(coefficients, b));
+		let root = if fa == 0.0 {
+			a
+		} else if fb == 0.0 {
+			b
+		} else if fa.signum() != fb.signum() {
+			refine(coefficients, &slope, a, b, fa)
+		} else {
+			continue
+		};
+
+		// A root right on an end would otherwise come up again from the next pair
+		if roots.last().is_none_or(|last| *last != root) {
+			roots.push(root);
+		}
+	}
+	roots
+}
+
+/// Narrows down the single root between `a` and `b`, where the polynomial is `fa` at `a` and changes sign before `b`
+fn refine(coefficients: &[f64], slope: &[f64], mut a: f64, mut b: f64, fa: f64) -> f64 {
+	let mut x = 0.5 * (a + b);
+	for _ in 0..100 {
+		let fx = evaluate(coefficients, x);
+		if fx == 0.0 {
+			return x
+		}
+
+		// Keep the bracket around the sign change
+		if fx.signum() == fa.signum() {
+			a = x;
+		} else {
+			b = x;
+		}
+		if b - a <= 1e-12 * f64::max(1.0, x.abs()) {
+			break
+		}
+
+		// Newton's step where it stays inside the bracket, bisection where it wanders off
+		let step = x - fx/evaluate(slope, x);
+		if a < step && step < b {
+			if (step - x).abs() <= 1e-14 * f64::max(1.0, x.abs()) {
+				return step
+			}
+			x = step;
+		} else {
+			x = 0.5 * (a + b);
+		}
+	}
+	x
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_roots(found: &[f64], expected: &[f64]) {
+		assert_eq!(found.len(), expected.len(), "found {found:?}, expected {expected:?}");
+		for (f, e) in found.iter().zip(expected) {
+			assert!((f - e).abs() <= 1e-9 * f64::max(1.0, e.abs()), "found {found:?}, expected {expected:?}");
+		}
+	}
+
+	/// The polynomial with exactly these roots
+	fn from_roots(roots: &[f64]) -> Vec<f64> {
+		roots.iter().fold(vec![1.0], |product, root| multiply(&product, &[-root, 1.0]))
+	}
+
+	#[test]
+	fn quadratic_roots() {
+		assert_roots(&roots_in(&from_roots(&[1.0, 3.0]), -10.0, 10.0), &[1.0, 3.0]);
+		assert_roots(&roots_in(&from_roots(&[1.0, 3.0]), 2.0, 10.0), &[3.0]);
+		// x² + 1 has none
+		assert_roots(&roots_in(&[1.0, 0.0, 1.0], -10.0, 10.0), &[]);
+	}
+
+	#[test]
+	fn quartic_roots() {
+		assert_roots(&roots_in(&from_roots(&[1.0, 2.0, 3.0, 4.0]), 0.0, 10.0), &[1.0, 2.0, 3.0, 4.0]);
+		assert_roots(&roots_in(&from_roots(&[-0.5, 0.25, 7.0, 100.0]), -1.0, 1000.0), &[-0.5, 0.25, 7.0, 100.0]);
+	}
+
+	#[test]
+	fn roots_on_the_ends() {
+		let coefficients = from_roots(&[1.0, 2.0, 3.0, 4.0]);
+		assert_roots(&roots_in(&coefficients, 1.0, 4.0), &[1.0, 2.0, 3.0, 4.0]);
+		assert_roots(&roots_in(&coefficients, 2.0, 3.0), &[2.0, 3.0]);
+		assert_roots(&roots_in(&from_roots(&[0.0, 2.0]), 0.0, 1.0), &[0.0]);
+	}
+
+	#[test]
+	fn torus_seen_edge_on() {
+		// A ray along the x axis through the ring (x² + R² - r²)² = 4R²x² with R = 2 and r = 0.5,
+		// starting far out at x = t - 100 so the roots come close together relative to their size
+		let x = [-100.0, 1.0];
+		let k = [x[0] * x[0] + 3.75, 2.0 * x[0], 1.0];
+		let coefficients: Vec<f64> = multiply(&k, &k).iter().zip(multiply(&x, &x).iter().chain([0.0; 2].iter()))
+			.map(|(a, b)| a - 16.0 * b)
+			.collect();
+		assert_roots(&roots_in(&coefficients, 0.0, 200.0), &[97.5, 98.5, 101.5, 102.5]);
+	}
+}
@@ -0,0 +1,284 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{color::Color, hittable::HitRecord, material::Material, microfacet::{local_directions, reflect, RoughDielectric, TrowbridgeReitz}, onb::Onb, rand::random_f64, ray::Ray, texture::{SolidColor, Texture}, vec3::{random_cosine_direction, Vec3}};
+
+/// (1 - cos)^5, the shape of Schlick's Fresnel approximation
+fn schlick_weight(cos: f64) -> f64 {
+	(1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+	a + (b - a) * t
+}
+
+fn luminance(c: &Color) -> f64 {
+	0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+/// The lobes `Principled` is made of, which one a scattered ray was sampled from
+#[derive(Clone, Copy)]
+enum Lobe {
+	Diffuse,
+	Specular,
+	Transmission,
+	Clearcoat
+}
+
+/// An uber-material after the Disney principled BSDF, with the usual parameters DCC tools export.
+/// Create it with `new` or `from_texture` and adjust the fields, every parameter goes from 0 to 1 except `ior`.
+///
+/// It blends a diffuse lobe (with retro-reflection, sheen and an approximation of subsurface scattering),
+/// a GGX specular lobe, a rough glass lobe for transmission, and a second, glossier GGX lobe for clearcoat.
+/// Once a ray has been transmitted into the object, only the glass lobe applies until it leaves again.
+pub struct Principled {
+	pub base_color: Arc<dyn Texture>,
+	// Conductor (1) or dielectric (0), metals tint their reflections with the base color and have no diffuse
+	pub metallic: f64,
+	pub roughness: f64,
+	// Dielectric reflectance at normal incidence, 0.5 is 4% like most plastics and glass
+	pub specular: f64,
+	// Tints dielectric reflections towards the base color
+	pub specular_tint: f64,
+	// Extra grazing reflection for cloth
+	pub sheen: f64,
+	pub sheen_tint: f64,
+	// Strength of a white varnish layer on top
+	pub clearcoat: f64,
+	// How polished the varnish is, 0 is satin and 1 is gloss
+	pub clearcoat_gloss: f64,
+	// Fraction of the dielectric part that is glass instead of diffuse
+	pub transmission: f64,
+	// Refraction index of the glass part
+	pub ior: f64,
+	// Flattens the diffuse lobe the way light scattering under the surface does, e.g for skin or wax
+	pub subsurface: f64,
+}
+
+impl Principled {
+	pub fn new(base_color: Color) -> Self {
+		Self::from_texture(Arc::new(SolidColor::new(base_color)))
+	}
+
+	pub fn from_texture(base_color: Arc<dyn Texture>) -> Self {
+		Self {
+			base_color,
+			metallic: 0.0,
+			roughness: 0.5,
+			specular: 0.5,
+			specular_tint: 0.0,
+			sheen: 0.0,
+			sheen_tint: 0.5,
+			clearcoat: 0.0,
+			clearcoat_gloss: 1.0,
+			transmission: 0.0,
+			ior: 1.5,
+			subsurface: 0.0,
+		}
+	}
+
+	fn glass(&self) -> RoughDielectric {
+		RoughDielectric::new(self.ior, self.roughness)
+	}
+
+	fn clearcoat_distribution(&self) -> TrowbridgeReitz {
+		TrowbridgeReitz::from_alpha(lerp(0.1, 0.001, self.clearcoat_gloss))
+	}
+
+	// Base color with its brightness taken out, for tinting
+	fn tint(base: &Color) -> Color {
+		let lum = luminance(base);
+		if lum > 0.0 {*base/lum} else {Color::from_xyz(1.0, 1.0, 1.0)}
+	}
+
+	/// How much each lobe contributes to the BSDF, for light arriving from outside.
+	/// The specular lobe is all of the reflection off the surface, for metals and dielectrics alike, so the diffuse
+	/// and transmission lobes only get what it lets through: the diffuse lobe is weighted by Fresnel in `diffuse`,
+	/// the transmission lobe only has the refracted part of the glass.
+	fn lobe_weights(&self) -> [(Lobe, f64); 4] {
+		let metallic = self.metallic.clamp(0.0, 1.0);
+		let transmission = self.transmission.clamp(0.0, 1.0);
+		[
+			(Lobe::Diffuse, (1.0 - metallic) * (1.0 - transmission)),
+			(Lobe::Specular, 1.0),
+			(Lobe::Transmission, (1.0 - metallic) * transmission),
+			(Lobe::Clearcoat, 0.25 * self.clearcoat.clamp(0.0, 1.0)),
+		]
+	}
+
+	fn diffuse(&self, base: &Color, wo: &Vec3, wi: &Vec3) -> Color {
+		if wi.z() <= 0.0 {
+			return Color::new()
+		}
+		let wh = Vec3::unit_vector(&(*wo + *wi));
+		let cos_d = Vec3::dot(wi, &wh);
+		let (fl, fv) = (schlick_weight(wi.z()), schlick_weight(wo.z()));
+
+		// Retro-reflection at grazing angles on rough surfaces
+		let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+		let fd = lerp(1.0, fd90, fl) * lerp(1.0, fd90, fv);
+
+		// Hanrahan-Krueger inspired flattening for subsurface
+		let fss90 = self.roughness * cos_d * cos_d;
+		let fss = lerp(1.0, fss90, fl) * lerp(1.0, fss90, fv);
+		let ss = 1.25 * (fss * (1.0/(wi.z() + wo.z()) - 0.5) + 0.5);
+
+		let sheen_color = (1.0 - self.sheen_tint) * Color::from_xyz(1.0, 1.0, 1.0) + self.sheen_tint * Self::tint(base);
+		let sheen = (self.sheen * schlick_weight(cos_d)) * sheen_color;
+
+		// What the specular lobe reflects on the way in or out doesn't make it to the diffuse layer
+		let f0 = luminance(&self.dielectric_f0(base));
+		let transmitted = (1.0 - lerp(f0, 1.0, schlick_weight(wo.z()))) * (1.0 - lerp(f0, 1.0, schlick_weight(wi.z())));
+
+		transmitted * (lerp(fd, ss, self.subsurface)/PI * *base + sheen) * wi.z()
+	}
+
+	fn dielectric_f0(&self, base: &Color) -> Color {
+		let tint = (1.0 - self.specular_tint) * Color::from_xyz(1.0, 1.0, 1.0) + self.specular_tint * Self::tint(base);
+		(0.08 * self.specular) * tint
+	}
+
+	fn specular_f0(&self, base: &Color) -> Color {
+		(1.0 - self.metallic) * self.dielectric_f0(base) + self.metallic * *base
+	}
+
+	/// A GGX reflection lobe with Schlick Fresnel, BSDF times cos_i
+	fn microfacet_reflection(distribution: &TrowbridgeReitz, f0: &Color, wo: &Vec3, wi: &Vec3) -> Color {
+		if wi.z() <= 0.0 {
+			return Color::new()
+		}
+		let wh = Vec3::unit_vector(&(*wo + *wi));
+		let fresnel = *f0 + schlick_weight(Vec3::dot(wo, &wh)) * (Color::from_xyz(1.0, 1.0, 1.0) - *f0);
+
+		fresnel * (distribution.d(&wh) * distribution.g(wo, wi)/(4.0 * wo.z()))
+	}
+
+	fn microfacet_pdf(distribution: &TrowbridgeReitz, wo: &Vec3, wi: &Vec3) -> f64 {
+		if wi.z() <= 0.0 {
+			return 0.0
+		}
+		let wh = Vec3::unit_vector(&(*wo + *wi));
+		distribution.visible_d(wo, &wh)/(4.0 * Vec3::dot(wo, &wh).abs())
+	}
+
+	/// The glass lobe, with the base color tinting what gets through. Each refraction tints by its square root,
+	/// so a ray going into an object and out again is tinted by the base color once.
+	fn transmission(&self, base: &Color, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		let f = self.glass().eval(r_in, rec, scattered);
+		if Vec3::dot(&scattered.direction(), &rec.normal) < 0.0 {
+			f * Color::from_xyz(base.x().max(0.0).sqrt(), base.y().max(0.0).sqrt(), base.z().max(0.0).sqrt())
+		} else {
+			f
+		}
+	}
+}
+
+impl Material for Principled {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		let uvw = Onb::new(&rec.normal);
+		let wo = uvw.to_local(&-Vec3::unit_vector(&r_in.direction()));
+		if wo.z() <= 0.0 {
+			return false
+		}
+
+		// Pick one lobe to sample from, then weigh the direction by the whole BSDF over the whole density
+		let lobe = if !rec.front_face {
+			Lobe::Transmission
+		} else {
+			let weights = self.lobe_weights();
+			let total: f64 = weights.iter().map(|(_, w)| w).sum();
+			let mut xi = random_f64() * total;
+			let mut picked = weights[0].0;
+			for (lobe, weight) in weights {
+				picked = lobe;
+				if xi < weight {
+					break
+				}
+				xi -= weight;
+			}
+			picked
+		};
+
+		let wi = match lobe {
+			Lobe::Diffuse => random_cosine_direction(),
+			Lobe::Specular => reflect(&wo, &TrowbridgeReitz::new(self.roughness).sample_wm(&wo)),
+			Lobe::Clearcoat => reflect(&wo, &self.clearcoat_distribution().sample_wm(&wo)),
+			Lobe::Transmission => {
+				let mut glass_attenuation = Color::new();
+				let mut glass_scattered = Ray::new();
+				if !self.glass().scatter(r_in, rec, &mut glass_attenuation, &mut glass_scattered) {
+					return false
+				}
+				uvw.to_local(&glass_scattered.direction())
+			}
+		};
+
+		*scattered = Ray::from_values(&rec.p, &uvw.transform(&wi));
+		let pdf = self.scattering_pdf(r_in, rec, scattered);
+		if pdf <= 0.0 {
+			return false
+		}
+		*attenuation = self.eval(r_in, rec, scattered)/pdf;
+		true
+	}
+
+	fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+		let base = self.base_color.value(rec.u, rec.v, &rec.p);
+		if !rec.front_face {
+			return self.transmission(&base, r_in, rec, scattered)
+		}
+
+		let (wo, wi) = local_directions(r_in, rec, scattered);
+		if wo.z() <= 0.0 {
+			return Color::new()
+		}
+
+		let mut f = Color::new();
+		for (lobe, weight) in self.lobe_weights() {
+			if weight <= 0.0 {
+				continue
+			}
+			let lobe_f = match lobe {
+				Lobe::Diffuse => self.diffuse(&base, &wo, &wi),
+				Lobe::Specular => Self::microfacet_reflection(&TrowbridgeReitz::new(self.roughness), &self.specular_f0(&base), &wo, &wi),
+				// Reflection off the glass is already in the specular lobe
+				Lobe::Transmission if wi.z() < 0.0 => self.transmission(&base, r_in, rec, scattered),
+				Lobe::Transmission => Color::new(),
+				Lobe::Clearcoat => Self::microfacet_reflection(&self.clearcoat_distribution(), &Color::from_xyz(0.04, 0.04, 0.04), &wo, &wi),
+			};
+			f += weight * lobe_f;
+		}
+		f
+	}
+
+	fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+		if !rec.front_face {
+			return self.glass().scattering_pdf(r_in, rec, scattered)
+		}
+
+		let (wo, wi) = local_directions(r_in, rec, scattered);
+		if wo.z() <= 0.0 {
+			return 0.0
+		}
+
+		let weights = self.lobe_weights();
+		let total: f64 = weights.iter().map(|(_, w)| w).sum();
+		let mut pdf = 0.0;
+		for (lobe, weight) in weights {
+			if weight <= 0.0 {
+				continue
+			}
+			let lobe_pdf = match lobe {
+				Lobe::Diffuse => f64::max(0.0, wi.z()/PI),
+				Lobe::Specular => Self::microfacet_pdf(&TrowbridgeReitz::new(self.roughness), &wo, &wi),
+				Lobe::Transmission => self.glass().scattering_pdf(r_in, rec, scattered),
+				Lobe::Clearcoat => Self::microfacet_pdf(&self.clearcoat_distribution(), &wo, &wi),
+			};
+			pdf += weight/total * lobe_pdf;
+		}
+		pdf
+	}
+
+	fn alpha(&self, rec: &HitRecord) -> f64 {
+		self.base_color.alpha(rec.u, rec.v, &rec.p)
+	}
+}
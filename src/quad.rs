@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use crate::{hittable::{HitRecord, Hittable}, interval::Interval, material::Material, rand::random_f64, ray::Ray, vec3::{Point3, Vec3}};
+
+// Parallel ray epsilon
+const E: f64 = 1e-8;
+
+/// A parallelogram with corner `q` and edges `u` and `v`, e.g a wall, a card or a rectangular light.
+/// Texture coordinates go from 0 to 1 along the edges, the normal is u × v.
+pub struct Quad {
+	q: Point3,
+	u: Vec3,
+	v: Vec3,
+	mat: Arc<dyn Material>,
+	// Unit normal and the plane's distance from the origin along it
+	normal: Vec3,
+	d: f64,
+	// n/(n·n) for the unnormalized normal n, turns a point on the plane into coordinates along u and v
+	w: Vec3,
+	area: f64,
+}
+
+impl Quad {
+	pub fn new(q: &Point3, u: &Vec3, v: &Vec3, mat: Arc<dyn Material>) -> Self {
+		let n = Vec3::cross(u, v);
+		let normal = Vec3::unit_vector(&n);
+		Self {
+			q: *q,
+			u: *u,
+			v: *v,
+			mat,
+			normal,
+			d: Vec3::dot(&normal, q),
+			w: n/Vec3::dot(&n, &n),
+			area: n.length(),
+		}
+	}
+
+	/// Coordinates of a point on the quad's plane along its edges, inside the quad when both are in [0, 1]
+	fn plane_coordinates(&self, p: &Point3) -> (f64, f64) {
+		let planar = *p - self.q;
+		(Vec3::dot(&self.w, &Vec3::cross(&planar, &self.v)), Vec3::dot(&self.w, &Vec3::cross(&self.u, &planar)))
+	}
+}
+
+impl Hittable for Quad {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		let denom = Vec3::dot(&self.normal, &r.direction());
+		if denom.abs() < E {
+			return false
+		}
+
+		let t = (self.d - Vec3::dot(&self.normal, &r.origin()))/denom;
+		if !ray_t.surrounds(t) {
+			return false
+		}
+
+		let p = r.at(t);
+		let (alpha, beta) = self.plane_coordinates(&p);
+		let unit = Interval::from_values(0.0, 1.0);
+		if !unit.contains(alpha) || !unit.contains(beta) {
+			return false
+		}
+
+		rec.t = t;
+		rec.p = p;
+		(rec.u, rec.v) = (alpha, beta);
+		(rec.tangent, rec.bitangent) = (self.u, self.v);
+		rec.set_face_normal(r, &self.normal);
+		rec.mat = self.mat.clone();
+
+		!rec.is_cut_out(r)
+	}
+
+	fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+		let mut rec = HitRecord::new();
+		if !self.hit(&Ray::from_values(origin, direction).ignoring_alpha(), &Interval::from_values(0.001, f64::INFINITY), &mut rec) {
+			return 0.0
+		}
+
+		// Area density turned into solid angle density as seen from the origin
+		let distance_squared = rec.t * rec.t * direction.length_squared();
+		let cosine = (Vec3::dot(direction, &self.normal)/direction.length()).abs();
+		distance_squared/(cosine * self.area)
+	}
+
+	fn random(&self, origin: &Point3) -> Vec3 {
+		let p = self.q + (random_f64() * self.u) + (random_f64() * self.v);
+		p - *origin
+	}
+
+	fn sample_surface(&self, rec: &mut HitRecord) -> f64 {
+		(rec.u, rec.v) = (random_f64(), random_f64());
+		rec.p = self.q + (rec.u * self.u) + (rec.v * self.v);
+		rec.normal = self.normal;
+		(rec.tangent, rec.bitangent) = (self.u, self.v);
+		rec.front_face = true;
+		rec.mat = self.mat.clone();
+
+		1.0/self.area
+	}
+
+	fn surface_pdf(&self, point: &Point3) -> f64 {
+		if (Vec3::dot(&self.normal, point) - self.d).abs() > 1e-6 * f64::max(1.0, self.d.abs()) {
+			return 0.0
+		}
+
+		let (alpha, beta) = self.plane_coordinates(point);
+		let unit = Interval::from_values(0.0, 1.0);
+		if !unit.contains(alpha) || !unit.contains(beta) {
+			return 0.0
+		}
+
+		1.0/self.area
+	}
+}
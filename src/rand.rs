@@ -81,8 +81,9 @@ pub fn random_f64_range(min: f64, max: f64) -> f64 {
 }
 
 // Random
+/// A seedable generator, for when results must be reproducible instead of drawn from the thread's stream
 #[derive(Clone, Copy)]
-struct Rand {
+pub struct Rand {
   x: u32, y: u32, z: u32, w: u32
 }
 
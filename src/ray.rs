@@ -1,21 +1,61 @@
 use crate::vec3::{Point3, Vec3};
 
+#[derive(Clone, Copy)]
 pub struct Ray {
 	orig: Point3,
-	dir: Vec3
+	dir: Vec3,
+	// In nanometers, only set when rendering spectrally
+	wavelength: Option<f64>,
+	// Scattered by a specular lobe of a material that also has non-specular ones, see `as_specular`
+	specular: bool,
+	// Hits surfaces whatever their material's alpha, see `ignoring_alpha`
+	ignore_alpha: bool
 }
 
 impl Ray {
 	pub fn new() -> Self {
-		Self { orig: Point3::new(), dir: Vec3::new() }
+		Self { orig: Point3::new(), dir: Vec3::new(), wavelength: None, specular: false, ignore_alpha: false }
 	}
 	pub fn from_values(origin: &Point3, direction: &Vec3) -> Self {
 		Self {
 			orig: *origin,
-			dir: *direction
+			dir: *direction,
+			wavelength: None,
+			specular: false,
+			ignore_alpha: false
 		}
 	}
 
+	/// The same ray, carrying light of a single wavelength in nanometers
+	pub fn with_wavelength(&self, wavelength: f64) -> Self {
+		Self { wavelength: Some(wavelength), ..*self }
+	}
+
+	pub fn wavelength(&self) -> Option<f64> {
+		self.wavelength
+	}
+
+	/// The same ray, marked as scattered by a specular lobe. Materials mixing specular and non-specular lobes
+	/// mark the rays they sample from the specular one, whose direction the other lobes could have picked too,
+	/// and return a density of 0 for them so integrators treat the bounce as specular.
+	pub fn as_specular(&self) -> Self {
+		Self { specular: true, ..*self }
+	}
+
+	pub fn is_specular(&self) -> bool {
+		self.specular
+	}
+
+	/// The same ray, hitting surfaces even where their material's alpha cuts them out. For finding the shape itself,
+	/// e.g a light's solid angle or a medium's boundary, which mustn't come and go at random.
+	pub fn ignoring_alpha(&self) -> Self {
+		Self { ignore_alpha: true, ..*self }
+	}
+
+	pub fn ignores_alpha(&self) -> bool {
+		self.ignore_alpha
+	}
+
 	pub fn origin(&self) -> Point3 {
 		return self.orig
 	}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use crate::{color::Color, hittable_list::HittableList, integrator::Integrator, light::Light, ray::Ray, sky::Sky, vec3::Vec3};
+
+/// Everything an integrator needs to know about what it is rendering
+pub struct Scene {
+	pub world: HittableList,
+	// Emissive objects that integrators may sample directly. These should also be added to `world`.
+	pub lights: HittableList,
+	// Point, spot and directional lights, which have no geometry and only light things through shadow rays
+	pub delta_lights: Vec<Arc<dyn Light>>,
+	// Daylight replacing the default sky gradient
+	pub sky: Option<Sky>,
+	// How the scene wants to be rendered, `--integrator <name>` on the command line overrides it. Path tracing if unset.
+	pub integrator: Option<Arc<dyn Integrator>>,
+}
+
+impl Scene {
+	pub fn from_world(world: HittableList) -> Self {
+		Self { world, lights: HittableList::new(), delta_lights: Vec::new(), sky: None, integrator: None }
+	}
+
+	/// Radiance arriving along a ray that escapes the scene
+	pub fn background(&self, r: &Ray) -> Color {
+		if let Some(sky) = &self.sky {
+			return sky.radiance(&r.direction())
+		}
+
+		// Scale ray direction to unit vector;
+		let unit_direction = Vec3::unit_vector(&r.direction()); // now -1.0 <= y <= 1.0
+		let a = 0.5 * (unit_direction.y() + 1.0); // a is the equivalent of y in the interval 0.0, 1.0, i,e 0.0 <= a <= 1.0
+		((1.0-a)*Color::from_xyz(1.0, 1.0, 1.0)) + (a*Color::from_xyz(0.5, 0.7, 1.0))
+	}
+}
@@ -0,0 +1,299 @@
+use std::sync::Arc;
+
+use crate::{hittable::{HitRecord, Hittable}, interval::Interval, material::Material, onb::Onb, ray::Ray, vec3::{Point3, Vec3}};
+
+// How close to the surface a point has to get to count as on it
+const HIT_DISTANCE: f64 = 1e-5;
+// Steps before a ray is given up on, it's crawling along right next to the surface without reaching it
+const MAX_STEPS: usize = 1000;
+// Offset for the central differences giving the normal
+const GRADIENT_STEP: f64 = 1e-6;
+// Smallest factor a shape can be scaled by
+const MIN_SCALE: f64 = 1e-6;
+
+/// A shape given by its signed distance, negative inside, built up from primitives centered on the origin.
+/// Move them with `translate`, `rotate` and `scale`, and combine them with the boolean operators,
+/// e.g `Sdf::rounded_box(..).smooth_union(Sdf::sphere(0.6).translate(..), 0.2)`.
+#[derive(Clone)]
+pub enum Sdf {
+	Sphere { radius: f64 },
+	// A box reaching `half_size` from the center, with the edges rounded off by `rounding`
+	RoundedBox { half_size: Vec3, rounding: f64 },
+	// A ring around the Y axis
+	Torus { major_radius: f64, minor_radius: f64 },
+	// The power 8 bulb is the classic, the distance is a lower bound estimated from the iteration's derivative
+	Mandelbulb { power: f64, iterations: u32 },
+	Translate { offset: Vec3, inner: Box<Sdf> },
+	// Rotates the inner shape by `angle` radians around `axis`
+	Rotate { axis: Vec3, angle: f64, inner: Box<Sdf> },
+	Scale { factor: f64, inner: Box<Sdf> },
+	Union(Box<Sdf>, Box<Sdf>),
+	Intersection(Box<Sdf>, Box<Sdf>),
+	Difference(Box<Sdf>, Box<Sdf>),
+	// A union blending the two shapes into each other over a width of about `smoothness`
+	SmoothUnion { a: Box<Sdf>, b: Box<Sdf>, smoothness: f64 },
+}
+
+impl Sdf {
+	pub fn sphere(radius: f64) -> Self {
+		Self::Sphere { radius: f64::max(0.0, radius) }
+	}
+
+	pub fn rounded_box(half_size: &Vec3, rounding: f64) -> Self {
+		let smallest = half_size.x().min(half_size.y()).min(half_size.z());
+		Self::RoundedBox { half_size: *half_size, rounding: rounding.clamp(0.0, f64::max(0.0, smallest)) }
+	}
+
+	pub fn torus(major_radius: f64, minor_radius: f64) -> Self {
+		Self::Torus { major_radius: f64::max(0.0, major_radius), minor_radius: f64::max(0.0, minor_radius) }
+	}
+
+	pub fn mandelbulb(power: f64, iterations: u32) -> Self {
+		Self::Mandelbulb { power, iterations }
+	}
+
+	pub fn translate(self, offset: &Vec3) -> Self {
+		Self::Translate { offset: *offset, inner: Box::new(self) }
+	}
+
+	/// Rotates the shape around `axis` through the origin by `angle` degrees, a zero axis leaves it as it is
+	pub fn rotate(self, axis: &Vec3, angle: f64) -> Self {
+		if axis.length_squared() == 0.0 {
+			return self
+		}
+		Self::Rotate { axis: Vec3::unit_vector(axis), angle: angle.to_radians(), inner: Box::new(self) }
+	}
+
+	/// Scales the shape up from the origin by `factor`, which is kept positive
+	pub fn scale(self, factor: f64) -> Self {
+		Self::Scale { factor: f64::max(MIN_SCALE, factor), inner: Box::new(self) }
+	}
+
+	pub fn union(self, other: Sdf) -> Self {
+		Self::Union(Box::new(self), Box::new(other))
+	}
+
+	pub fn intersection(self, other: Sdf) -> Self {
+		Self::Intersection(Box::new(self), Box::new(other))
+	}
+
+	/// This shape with `other` carved out of it
+	pub fn difference(self, other: Sdf) -> Self {
+		Self::Difference(Box::new(self), Box::new(other))
+	}
+
+	pub fn smooth_union(self, other: Sdf, smoothness: f64) -> Self {
+		Self::SmoothUnion { a: Box::new(self), b: Box::new(other), smoothness: f64::max(0.0, smoothness) }
+	}
+
+	/// Signed distance from `p` to the surface, or a lower bound of it for the shapes that can't tell exactly
+	pub fn distance(&self, p: &Point3) -> f64 {
+		match self {
+			Self::Sphere { radius } => p.length() - radius,
+			Self::RoundedBox { half_size, rounding } => {
+				let q = Vec3::from_xyz(
+					p.x().abs() - half_size.x() + rounding,
+					p.y().abs() - half_size.y() + rounding,
+					p.z().abs() - half_size.z() + rounding,
+				);
+				let outside = Vec3::from_xyz(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).length();
+				let inside = f64::min(q.x().max(q.y()).max(q.z()), 0.0);
+				outside + inside - rounding
+			}
+			Self::Torus { major_radius, minor_radius } => {
+				let ring = f64::sqrt(p.x() * p.x() + p.z() * p.z()) - major_radius;
+				f64::sqrt(ring * ring + p.y() * p.y()) - minor_radius
+			}
+			Self::Mandelbulb { power, iterations } => Self::mandelbulb_distance(p, *power, *iterations),
+			Self::Translate { offset, inner } => inner.distance(&(*p - *offset)),
+			Self::Rotate { axis, angle, inner } => {
+				// Turn the point the other way instead of the shape (Rodrigues' rotation formula)
+				let (sin, cos) = (-angle).sin_cos();
+				let rotated = cos * *p + sin * Vec3::cross(axis, p) + (1.0 - cos) * Vec3::dot(axis, p) * *axis;
+				inner.distance(&rotated)
+			}
+			Self::Scale { factor, inner } => inner.distance(&(*p/(*factor))) * factor,
+			Self::Union(a, b) => f64::min(a.distance(p), b.distance(p)),
+			Self::Intersection(a, b) => f64::max(a.distance(p), b.distance(p)),
+			Self::Difference(a, b) => f64::max(a.distance(p), -b.distance(p)),
+			Self::SmoothUnion { a, b, smoothness } => {
+				let (da, db) = (a.distance(p), b.distance(p));
+				if *smoothness <= 0.0 {
+					return f64::min(da, db)
+				}
+				// Polynomial smooth minimum
+				let h = (0.5 + 0.5 * (db - da)/smoothness).clamp(0.0, 1.0);
+				db + (da - db) * h - smoothness * h * (1.0 - h)
+			}
+		}
+	}
+
+	fn mandelbulb_distance(p: &Point3, power: f64, iterations: u32) -> f64 {
+		let mut z = *p;
+		let mut dr = 1.0;
+		let mut r = z.length();
+		for _ in 0..iterations {
+			if !(1e-12..=2.0).contains(&r) {
+				break
+			}
+
+			// z → z^power + p in spherical coordinates, tracking the running derivative
+			let theta = f64::acos(z.z()/r) * power;
+			let phi = f64::atan2(z.y(), z.x()) * power;
+			dr = r.powf(power - 1.0) * power * dr + 1.0;
+			let zr = r.powf(power);
+			z = zr * Vec3::from_xyz(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) + *p;
+			r = z.length();
+		}
+		if r < 1e-12 {
+			return 0.0
+		}
+		0.5 * r.ln() * r/dr
+	}
+
+	/// Radius around the origin the whole shape fits in
+	fn bounding_radius(&self) -> f64 {
+		match self {
+			Self::Sphere { radius } => *radius,
+			Self::RoundedBox { half_size, .. } => half_size.length(),
+			Self::Torus { major_radius, minor_radius } => major_radius + minor_radius,
+			// Anything further out escapes on the first iteration
+			Self::Mandelbulb { .. } => 2.0,
+			Self::Translate { offset, inner } => offset.length() + inner.bounding_radius(),
+			Self::Rotate { inner, .. } => inner.bounding_radius(),
+			Self::Scale { factor, inner } => factor * inner.bounding_radius(),
+			Self::Union(a, b) => f64::max(a.bounding_radius(), b.bounding_radius()),
+			Self::Intersection(a, b) => f64::min(a.bounding_radius(), b.bounding_radius()),
+			Self::Difference(a, _) => a.bounding_radius(),
+			Self::SmoothUnion { a, b, smoothness } => f64::max(a.bounding_radius(), b.bounding_radius()) + smoothness,
+		}
+	}
+}
+
+/// A shape drawn by sphere tracing its signed distance `sdf`, e.g rounded or blended shapes and fractals
+/// that would take a lot of triangles otherwise. It has no texture coordinates, so use solid textures.
+pub struct SdfObject {
+	sdf: Sdf,
+	mat: Arc<dyn Material>,
+	bounding_radius: f64,
+}
+
+impl SdfObject {
+	pub fn new(sdf: Sdf, mat: Arc<dyn Material>) -> Self {
+		let bounding_radius = sdf.bounding_radius();
+		Self { sdf, mat, bounding_radius }
+	}
+
+	/// The surface normal from the gradient of the distance
+	fn normal(&self, p: &Point3) -> Vec3 {
+		let gradient = Vec3::from_xyz(
+			self.sdf.distance(&(*p + Vec3::from_xyz(GRADIENT_STEP, 0.0, 0.0))) - self.sdf.distance(&(*p - Vec3::from_xyz(GRADIENT_STEP, 0.0, 0.0))),
+			self.sdf.distance(&(*p + Vec3::from_xyz(0.0, GRADIENT_STEP, 0.0))) - self.sdf.distance(&(*p - Vec3::from_xyz(0.0, GRADIENT_STEP, 0.0))),
+			self.sdf.distance(&(*p + Vec3::from_xyz(0.0, 0.0, GRADIENT_STEP))) - self.sdf.distance(&(*p - Vec3::from_xyz(0.0, 0.0, GRADIENT_STEP))),
+		);
+		if gradient.length_squared() == 0.0 {
+			return Vec3::from_xyz(0.0, 1.0, 0.0)
+		}
+		Vec3::unit_vector(&gradient)
+	}
+
+	/// Where the ray is inside the bounding sphere, if it gets there at all
+	fn bounds(&self, r: &Ray) -> Option<(f64, f64)> {
+		let a = r.direction().length_squared();
+		let h = Vec3::dot(&r.direction(), &-r.origin());
+		let c = r.origin().length_squared() - self.bounding_radius * self.bounding_radius;
+		let discriminant = h * h - a * c;
+		if discriminant < 0.0 {
+			return None
+		}
+		let sqrtd = discriminant.sqrt();
+		Some(((h - sqrtd)/a, (h + sqrtd)/a))
+	}
+}
+
+impl Hittable for SdfObject {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		let Some((enter, exit)) = self.bounds(r) else {
+			return false
+		};
+		let end = f64::min(ray_t.max, exit);
+		let length = r.direction().length();
+
+		// Step along the ray by the distance to the surface, the ray can't cross it in that far.
+		// Rays starting inside, e.g refracted ones, march the same way towards where they leave.
+		let mut t = f64::max(ray_t.min, enter);
+		// Set while going through a cut out hit, until the ray is clear of the surface again
+		let mut crossing = false;
+		for _ in 0..MAX_STEPS {
+			if t >= end {
+				return false
+			}
+
+			let distance = self.sdf.distance(&r.at(t)).abs();
+			if distance < HIT_DISTANCE && crossing {
+				t += HIT_DISTANCE/length;
+				continue
+			}
+			crossing = false;
+			if distance < HIT_DISTANCE {
+				// Too close to where the ray starts, e.g a grazing reflection off this surface, carry on past it
+				if !ray_t.surrounds(t) {
+					crossing = true;
+					t += HIT_DISTANCE/length;
+					continue
+				}
+
+				rec.t = t;
+				rec.p = r.at(t);
+				let outward_normal = self.normal(&rec.p);
+				let uvw = Onb::new(&outward_normal);
+				(rec.u, rec.v) = (0.0, 0.0);
+				(rec.tangent, rec.bitangent) = (uvw.u(), uvw.v());
+				rec.set_face_normal(r, &outward_normal);
+				rec.mat = self.mat.clone();
+
+				if !rec.is_cut_out(r) {
+					return true
+				}
+				crossing = true;
+				t += HIT_DISTANCE/length;
+				continue
+			}
+
+			t += distance/length;
+		}
+
+		false
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::material::blank_material;
+
+	fn hit_t(sdf: Sdf, r: &Ray) -> Option<f64> {
+		let mut rec = HitRecord::new();
+		SdfObject::new(sdf, blank_material()).hit(r, &Interval::from_values(0.001, f64::INFINITY), &mut rec).then_some(rec.t)
+	}
+
+	#[test]
+	fn unit_sphere_hit() {
+		let r = Ray::from_values(&Point3::from_xyz(0.0, 0.0, 2.0), &Vec3::from_xyz(0.0, 0.0, -1.0));
+		let t = hit_t(Sdf::sphere(1.0), &r).unwrap();
+		assert!((t - 1.0).abs() < 1e-4, "hit at {t}");
+
+		// The same sphere made from a smaller one, rotated about nothing
+		let t = hit_t(Sdf::sphere(0.5).scale(2.0).rotate(&Vec3::new(), 45.0), &r).unwrap();
+		assert!((t - 1.0).abs() < 1e-4, "hit at {t}");
+	}
+
+	#[test]
+	fn degenerate_scale_and_rotation() {
+		// Scaled down to next to nothing, a ray just missing the origin misses it
+		let r = Ray::from_values(&Point3::from_xyz(0.1, 0.0, 2.0), &Vec3::from_xyz(0.0, 0.0, -1.0));
+		assert!(Sdf::sphere(1.0).scale(0.0).distance(&Point3::new()).is_finite());
+		assert!(hit_t(Sdf::sphere(1.0).scale(-1.0), &r).is_none());
+		assert_eq!(Sdf::sphere(1.0).rotate(&Vec3::new(), 30.0).distance(&Point3::new()), -1.0);
+	}
+}
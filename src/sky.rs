@@ -0,0 +1,156 @@
+use std::f64::consts::PI;
+
+use crate::{color::Color, onb::Onb, rand::random_f64, spectrum::xyz_to_rgb, vec3::Vec3};
+
+// Angular radius of the sun as seen from the earth, in radians
+const SUN_ANGULAR_RADIUS: f64 = 0.004652;
+// Luminance of the sun before it goes through the atmosphere, in kcd/m²
+const SUN_LUMINANCE: f64 = 2.0e6;
+// Luminances come out in kcd/m², this makes a white diffuse surface facing a high sun come out at around 1
+const SCALE: f64 = 0.03;
+// Wavelengths standing in for the red, green and blue channels when attenuating sunlight, in micrometers
+const RGB_WAVELENGTHS: [f64; 3] = [0.680, 0.550, 0.440];
+
+/// Coefficients of the Perez et al. sky luminance distribution
+#[derive(Clone, Copy)]
+struct Perez {
+	a: f64,
+	b: f64,
+	c: f64,
+	d: f64,
+	e: f64,
+}
+
+impl Perez {
+	/// Relative brightness of the sky at `cos_theta` from the zenith and `gamma` radians from the sun
+	fn f(&self, cos_theta: f64, gamma: f64) -> f64 {
+		let cos_gamma = gamma.cos();
+		(1.0 + self.a * f64::exp(self.b/cos_theta)) * (1.0 + self.c * f64::exp(self.d * gamma) + self.e * cos_gamma * cos_gamma)
+	}
+}
+
+/// A clear daytime sky after Preetham, Shirley and Smits' analytic model (1999), together with the sun.
+/// Turbidity says how hazy the air is, 2 is very clear, 3 is a typical clear day and 6 or more is hazy and warm.
+///
+/// Put it in `Scene::sky` to replace the default gradient. Integrators sample the sun disk directly,
+/// since BSDF sampling alone would rarely find something so small and so bright.
+pub struct Sky {
+	// Unit vector towards the sun
+	sun_direction: Vec3,
+	// Perez coefficients for luminance Y and chromaticities x and y
+	perez: [Perez; 3],
+	// Y, x and y at the zenith, divided by the Perez function there so the sky can be scaled in one go
+	zenith: [f64; 3],
+	sun_radiance: Color,
+	cos_sun_radius: f64,
+}
+
+impl Sky {
+	/// `elevation` of the sun above the horizon and its `azimuth`, measured from -Z towards +X, are in degrees.
+	/// Elevations below the horizon are treated as the sun just setting.
+	pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Self {
+		let elevation = elevation.clamp(0.0, 90.0).to_radians();
+		let azimuth = azimuth.to_radians();
+		let sun_direction = Vec3::from_xyz(elevation.cos() * azimuth.sin(), elevation.sin(), -elevation.cos() * azimuth.cos());
+
+		let t = turbidity.clamp(1.7, 10.0);
+		let theta_s = PI/2.0 - elevation;
+		let perez = [
+			Perez { a: 0.1787 * t - 1.4630, b: -0.3554 * t + 0.4275, c: -0.0227 * t + 5.3251, d: 0.1206 * t - 2.5771, e: -0.0670 * t + 0.3703 },
+			Perez { a: -0.0193 * t - 0.2592, b: -0.0665 * t + 0.0008, c: -0.0004 * t + 0.2125, d: -0.0641 * t - 0.8989, e: -0.0033 * t + 0.0452 },
+			Perez { a: -0.0167 * t - 0.2608, b: -0.0950 * t + 0.0092, c: -0.0079 * t + 0.2102, d: -0.0441 * t - 1.6537, e: -0.0109 * t + 0.0529 },
+		];
+
+		let chi = (4.0/9.0 - t/120.0) * (PI - 2.0 * theta_s);
+		let zenith_y = f64::max(0.0, (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192);
+		let (th, th2, th3) = (theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+		let zenith_x = t * t * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+			+ t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+			+ (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+		let zenith_chroma_y = t * t * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+			+ t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+			+ (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+		let cos_theta_s = f64::max(theta_s.cos(), 0.01);
+		let zenith = [
+			zenith_y/perez[0].f(1.0, theta_s),
+			zenith_x/perez[1].f(1.0, theta_s),
+			zenith_chroma_y/perez[2].f(1.0, theta_s),
+		];
+
+		Self {
+			sun_direction,
+			perez,
+			zenith,
+			sun_radiance: Self::attenuated_sun(cos_theta_s, theta_s, t),
+			cos_sun_radius: SUN_ANGULAR_RADIUS.cos(),
+		}
+	}
+
+	/// Sunlight after Rayleigh scattering and haze on its way down through the atmosphere
+	fn attenuated_sun(cos_theta_s: f64, theta_s: f64, turbidity: f64) -> Color {
+		// Relative optical mass, how much more air the light goes through than straight down (Kasten and Young)
+		let mass = 1.0/(cos_theta_s + 0.15 * f64::powf(93.885 - theta_s.to_degrees(), -1.253));
+		// Angstrom's turbidity formula for the haze
+		let beta = 0.04608 * turbidity - 0.04586;
+
+		let [r, g, b] = RGB_WAVELENGTHS.map(|lambda| {
+			let rayleigh = f64::exp(-0.008735 * mass * lambda.powf(-4.08));
+			let aerosol = f64::exp(-beta * mass * lambda.powf(-1.3));
+			SUN_LUMINANCE * SCALE * rayleigh * aerosol
+		});
+		Color::from_xyz(r, g, b)
+	}
+
+	pub fn sun_direction(&self) -> Vec3 {
+		self.sun_direction
+	}
+
+	/// Radiance of the sky alone along `direction`, without the sun disk.
+	/// Below the horizon it keeps the color of the horizon.
+	pub fn sky_radiance(&self, direction: &Vec3) -> Color {
+		let direction = Vec3::unit_vector(direction);
+		let cos_theta = f64::max(direction.y(), 0.01);
+		let gamma = f64::acos(Vec3::dot(&direction, &self.sun_direction).clamp(-1.0, 1.0));
+
+		let luminance = self.zenith[0] * self.perez[0].f(cos_theta, gamma);
+		let x = self.zenith[1] * self.perez[1].f(cos_theta, gamma);
+		let y = self.zenith[2] * self.perez[2].f(cos_theta, gamma);
+		if y <= 0.0 || luminance <= 0.0 {
+			return Color::new()
+		}
+
+		let xyz = Vec3::from_xyz(x/y * luminance, luminance, (1.0 - x - y)/y * luminance);
+		let rgb = SCALE * xyz_to_rgb(&xyz);
+		Color::from_xyz(f64::max(0.0, rgb.x()), f64::max(0.0, rgb.y()), f64::max(0.0, rgb.z()))
+	}
+
+	/// Radiance of the sun disk along `direction`, black outside of it
+	pub fn sun_radiance(&self, direction: &Vec3) -> Color {
+		if Vec3::dot(&Vec3::unit_vector(direction), &self.sun_direction) < self.cos_sun_radius {
+			return Color::new()
+		}
+		self.sun_radiance
+	}
+
+	/// Everything arriving along `direction`, sky and sun
+	pub fn radiance(&self, direction: &Vec3) -> Color {
+		self.sky_radiance(direction) + self.sun_radiance(direction)
+	}
+
+	/// A direction towards a uniformly random point on the sun disk
+	pub fn sample_sun(&self) -> Vec3 {
+		let z = 1.0 - random_f64() * (1.0 - self.cos_sun_radius);
+		let phi = 2.0 * PI * random_f64();
+		let r = f64::sqrt(f64::max(0.0, 1.0 - z * z));
+		Onb::new(&self.sun_direction).transform(&Vec3::from_xyz(r * phi.cos(), r * phi.sin(), z))
+	}
+
+	/// Solid angle density of `sample_sun` picking `direction`
+	pub fn sun_pdf(&self, direction: &Vec3) -> f64 {
+		if Vec3::dot(&Vec3::unit_vector(direction), &self.sun_direction) < self.cos_sun_radius {
+			return 0.0
+		}
+		1.0/(2.0 * PI * (1.0 - self.cos_sun_radius))
+	}
+}
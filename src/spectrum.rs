@@ -0,0 +1,136 @@
+use std::sync::OnceLock;
+
+use crate::{color::Color, vec3::Vec3};
+
+/// Wavelengths rendered in spectral mode, in nanometers
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 720.0;
+
+// Smits' basis spectra for RGB to spectrum conversion, in 10 equal bins over LAMBDA_MIN..LAMBDA_MAX
+const WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+/// Picks a wavelength uniformly over the rendered range, returning it and its density
+pub fn sample_wavelength(u: f64) -> (f64, f64) {
+	(LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN), 1.0/(LAMBDA_MAX - LAMBDA_MIN))
+}
+
+/// Value at `wavelength` of a smooth spectrum that looks like the RGB color `c` (Smits 1999).
+/// Works for reflectances as well as for emission, the conversion is linear in the color's brightness.
+pub fn rgb_to_spectrum(c: &Color, wavelength: f64) -> f64 {
+	let bin = (((wavelength - LAMBDA_MIN)/(LAMBDA_MAX - LAMBDA_MIN) * 10.0) as usize).min(9);
+	let (r, g, b) = (c.x(), c.y(), c.z());
+
+	// The smallest channel goes to white, the middle one to the secondary color and the rest to the primary
+	if r <= g && r <= b {
+		let base = r * WHITE[bin];
+		if g <= b {base + (g - r) * CYAN[bin] + (b - g) * BLUE[bin]} else {base + (b - r) * CYAN[bin] + (g - b) * GREEN[bin]}
+	} else if g <= r && g <= b {
+		let base = g * WHITE[bin];
+		if r <= b {base + (r - g) * MAGENTA[bin] + (b - r) * BLUE[bin]} else {base + (b - g) * MAGENTA[bin] + (r - b) * RED[bin]}
+	} else {
+		let base = b * WHITE[bin];
+		if r <= g {base + (r - b) * YELLOW[bin] + (g - r) * GREEN[bin]} else {base + (g - b) * YELLOW[bin] + (r - g) * RED[bin]}
+	}
+}
+
+// Piecewise gaussian used in the matching function fits
+fn gaussian(x: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+	let t = (x - mu)/if x < mu {sigma_low} else {sigma_high};
+	f64::exp(-0.5 * t * t)
+}
+
+/// The CIE 1931 color matching functions, from the multi-lobe fit of Wyman, Sloan and Shirley (2013)
+pub fn wavelength_to_xyz(wavelength: f64) -> Vec3 {
+	let x = 1.056 * gaussian(wavelength, 599.8, 37.9, 31.0)
+		+ 0.362 * gaussian(wavelength, 442.0, 16.0, 26.7)
+		- 0.065 * gaussian(wavelength, 501.1, 20.4, 26.2);
+	let y = 0.821 * gaussian(wavelength, 568.8, 46.9, 40.5)
+		+ 0.286 * gaussian(wavelength, 530.9, 16.3, 31.1);
+	let z = 1.217 * gaussian(wavelength, 437.0, 11.8, 36.0)
+		+ 0.681 * gaussian(wavelength, 459.0, 26.0, 13.8);
+
+	Vec3::from_xyz(x, y, z)
+}
+
+/// CIE XYZ to linear sRGB
+pub fn xyz_to_rgb(xyz: &Vec3) -> Color {
+	Color::from_xyz(
+		3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+		-0.9692660 * xyz.x() + 1.8760108 * xyz.y() + 0.0415560 * xyz.z(),
+		0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+	)
+}
+
+/// The RGB a flat spectrum of 1 integrates to, what spectral results get divided by so that
+/// white surfaces under white light stay white like in RGB mode
+fn white_balance() -> Color {
+	static WHITE_RGB: OnceLock<Color> = OnceLock::new();
+	*WHITE_RGB.get_or_init(|| {
+		let steps = 1000;
+		let step = (LAMBDA_MAX - LAMBDA_MIN)/steps as f64;
+		let mut xyz = Vec3::new();
+		for i in 0..steps {
+			xyz += step * wavelength_to_xyz(LAMBDA_MIN + (i as f64 + 0.5) * step);
+		}
+		xyz_to_rgb(&xyz)
+	})
+}
+
+/// Turns radiance carried at a single sampled wavelength into an RGB estimate for the film,
+/// going through XYZ. Averaging these over many wavelengths converges to the spectrum's color.
+pub fn sample_to_rgb(radiance: f64, wavelength: f64, pdf: f64) -> Color {
+	if pdf <= 0.0 {
+		return Color::new()
+	}
+	let rgb = xyz_to_rgb(&(radiance/pdf * wavelength_to_xyz(wavelength)));
+	let white = white_balance();
+	Color::from_xyz(rgb.x()/white.x(), rgb.y()/white.y(), rgb.z()/white.z())
+}
+
+/// How a dielectric's refraction index changes with wavelength, which is what splits white light
+/// into colors in prisms and diamonds
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+	// n = a + b/λ², λ in micrometers
+	Cauchy { a: f64, b: f64 },
+	// n² = 1 + Σ b_i λ²/(λ² - c_i), λ in micrometers
+	Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+	/// Borosilicate crown glass, common optical glass
+	pub fn bk7() -> Self {
+		Self::Sellmeier { b: [1.03961212, 0.231792344, 1.01046945], c: [0.00600069867, 0.0200179144, 103.560653] }
+	}
+
+	pub fn fused_silica() -> Self {
+		Self::Sellmeier { b: [0.6961663, 0.4079426, 0.8974794], c: [0.00467914826, 0.0135120631, 97.9340025] }
+	}
+
+	pub fn diamond() -> Self {
+		Self::Sellmeier { b: [0.3306, 4.3356, 0.0], c: [0.030625, 0.011236, 0.0] }
+	}
+
+	/// Dense flint glass, disperses a lot more than crown glass
+	pub fn flint() -> Self {
+		Self::Cauchy { a: 1.7280, b: 0.01342 }
+	}
+
+	/// Refraction index at `wavelength` in nanometers
+	pub fn ior(&self, wavelength: f64) -> f64 {
+		let l2 = (wavelength/1000.0) * (wavelength/1000.0);
+		match self {
+			Dispersion::Cauchy { a, b } => a + b/l2,
+			Dispersion::Sellmeier { b, c } => {
+				let n2 = 1.0 + (0..3).map(|i| b[i] * l2/(l2 - c[i])).sum::<f64>();
+				n2.sqrt()
+			}
+		}
+	}
+}
@@ -1,6 +1,6 @@
-use std::{sync::{Arc, Mutex}};
+use std::{f64::consts::PI, sync::{Arc, Mutex}};
 
-use crate::{hittable::{HitRecord, Hittable}, interval::Interval, material::{Material}, ray::Ray, vec3::{Point3, Vec3}};
+use crate::{hittable::{HitRecord, Hittable, Span}, interval::Interval, material::{Material}, onb::Onb, rand::random_f64, ray::Ray, vec3::{random_unit_vector, Point3, Vec3}};
 
 pub struct Sphere {
   center: Point3,
@@ -12,12 +12,50 @@ impl Sphere {
   	pub fn new(center: &Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
     	Self {center: center.clone(), radius: f64::max(0.0, radius), mat}
   	}
+
+	/// Texture coordinates of a point on the unit sphere: u goes around the Y axis starting from X = -1,
+	/// v goes from Y = -1 at the bottom to Y = +1 at the top
+	fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+		let theta = f64::acos(-p.y());
+		let phi = f64::atan2(-p.z(), p.x()) + PI;
+
+		(phi/(2.0 * PI), theta/PI)
+	}
+
+	/// ∂p/∂u and ∂p/∂v for the mapping in `get_sphere_uv`, at the point with outward normal `n`
+	fn get_sphere_tangents(&self, n: &Vec3) -> (Vec3, Vec3) {
+		let sin_theta = f64::sqrt(n.x() * n.x() + n.z() * n.z());
+		if sin_theta < 1e-9 {
+			// u is undefined at the poles, any frame around the normal will do
+			let uvw = Onb::new(n);
+			return (2.0 * PI * self.radius * uvw.u(), PI * self.radius * uvw.v())
+		}
+
+		let dpdu = 2.0 * PI * self.radius * Vec3::from_xyz(n.z(), 0.0, -n.x());
+		let dpdv = PI * self.radius * Vec3::from_xyz(-n.x() * n.y()/sin_theta, sin_theta, -n.y() * n.z()/sin_theta);
+		(dpdu, dpdv)
+	}
+
+	/// Fills in `rec` for a hit at `t`
+	fn set_hit(&self, r: &Ray, t: f64, rec: &mut HitRecord) {
+		rec.t = t;
+		rec.p = r.at(rec.t);
+		let outward_normal = (rec.p - self.center)/self.radius;
+		rec.set_face_normal(r, &outward_normal);
+		(rec.u, rec.v) = Self::get_sphere_uv(&outward_normal);
+		(rec.tangent, rec.bitangent) = self.get_sphere_tangents(&outward_normal);
+		rec.mat = self.mat.clone();
+	}
 }
 
 impl Hittable for Sphere {
 	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
 		let center = self.center;
 		let radius = self.radius;
+		// A sphere without size has no surface to hit
+		if radius <= 0.0 {
+			return false
+		}
       
 		// get (C - Q) and store
 		// Assume b in the quadratic eqn = -2h, you'd see how we got a c, h
@@ -33,23 +71,122 @@ impl Hittable for Sphere {
 		
 		let sqrtd = discriminant.sqrt();
 
-		// Find the nearest root that lies in the acceptable range
-		let mut root = (h-sqrtd)/a;
-		if !ray_t.surrounds(root) {
-			root = (h + sqrtd)/a;
-
+		// Find the nearest root that lies in the acceptable range and isn't cut out by the material's alpha
+		for root in [(h - sqrtd)/a, (h + sqrtd)/a] {
 			if !ray_t.surrounds(root) {
-				return false
+				continue
+			}
+
+			self.set_hit(r, root, rec);
+			if !rec.is_cut_out(r) {
+				return true
 			}
 		}
 
-		rec.t = root;
-		rec.p = r.at(rec.t);
-		let outward_normal = (rec.p - center)/radius;
-		rec.set_face_normal(&r, &outward_normal);
+		false
+	}
+
+	fn spans(&self, r: &Ray) -> Vec<Span> {
+		if self.radius <= 0.0 {
+			return Vec::new()
+		}
+		let oc = self.center - r.origin();
+		let a = r.direction().length_squared();
+		let h = Vec3::dot(&r.direction(), &oc);
+		let c = oc.length_squared() - (self.radius * self.radius);
+		let discriminant = h*h - a*c;
+		if discriminant < 0.0 {
+			return Vec::new()
+		}
+
+		let sqrtd = discriminant.sqrt();
+		let (mut enter, mut exit) = (HitRecord::new(), HitRecord::new());
+		self.set_hit(r, (h - sqrtd)/a, &mut enter);
+		self.set_hit(r, (h + sqrtd)/a, &mut exit);
+		vec![Span { enter, exit }]
+	}
+
+	fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+		// This only works for stationary spheres seen from outside
+		let mut rec = HitRecord::new();
+		if !self.hit(&Ray::from_values(origin, direction).ignoring_alpha(), &Interval::from_values(0.001, f64::INFINITY), &mut rec) {
+			return 0.0
+		}
+
+		let distance_squared = (self.center - *origin).length_squared();
+		if distance_squared <= self.radius * self.radius {
+			// origin is inside the sphere, so `random` falls back to the whole sphere of directions
+			return 1.0/(4.0 * PI)
+		}
+
+		let cos_theta_max = f64::sqrt(1.0 - self.radius * self.radius/distance_squared);
+		let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+		1.0/solid_angle
+	}
+
+	fn random(&self, origin: &Point3) -> Vec3 {
+		let direction = self.center - *origin;
+		let distance_squared = direction.length_squared();
+		if distance_squared <= self.radius * self.radius {
+			return random_unit_vector()
+		}
+
+		let uvw = Onb::new(&direction);
+		uvw.transform(&random_to_sphere(self.radius, distance_squared))
+	}
+
+	fn sample_surface(&self, rec: &mut HitRecord) -> f64 {
+		let outward_normal = random_unit_vector();
+		rec.p = self.center + self.radius * outward_normal;
+		rec.normal = outward_normal;
+		(rec.u, rec.v) = Self::get_sphere_uv(&outward_normal);
+		rec.front_face = true;
 		rec.mat = self.mat.clone();
 
-		return true;
+		1.0/(4.0 * PI * self.radius * self.radius)
+	}
+
+	fn surface_pdf(&self, point: &Point3) -> f64 {
+		let distance = (*point - self.center).length();
+		if (distance - self.radius).abs() > 1e-6 * f64::max(1.0, self.radius) {
+			return 0.0
+		}
 
+		1.0/(4.0 * PI * self.radius * self.radius)
 	}
-}
\ No newline at end of file
+}
+
+/// Returns a direction around +z, uniformly distributed over the cone subtended by a sphere
+/// of the given radius at the given (squared) distance
+fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
+	let r1 = random_f64();
+	let r2 = random_f64();
+	let z = 1.0 + r2 * (f64::sqrt(1.0 - radius * radius/distance_squared) - 1.0);
+
+	let phi = 2.0 * PI * r1;
+	let x = f64::cos(phi) * f64::sqrt(1.0 - z * z);
+	let y = f64::sin(phi) * f64::sqrt(1.0 - z * z);
+
+	Vec3::from_xyz(x, y, z)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{alpha_mask::AlphaMasked, color::Color, material::Lambertian};
+
+	#[test]
+	fn cut_out_light_keeps_its_pdf() {
+		let mat = Arc::new(AlphaMasked::from_value(Arc::new(Lambertian::new(Color::from_xyz(1.0, 1.0, 1.0))), 0.5));
+		let sphere = Sphere::new(&Point3::from_xyz(0.0, 0.0, -5.0), 1.0, mat);
+		let (origin, direction) = (Point3::new(), Vec3::from_xyz(0.0, 0.0, -1.0));
+		let r = Ray::from_values(&origin, &direction);
+		let ray_t = Interval::from_values(0.001, f64::INFINITY);
+
+		// Rays see through it about half the time, the light's density mustn't
+		let misses = (0..100).filter(|_| !sphere.hit(&r, &ray_t, &mut HitRecord::new())).count();
+		assert!(misses > 0);
+		assert!((0..100).all(|_| sphere.pdf_value(&origin, &direction) > 0.0));
+	}
+}
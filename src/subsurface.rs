@@ -0,0 +1,107 @@
+use crate::{color::Color, hittable::HitRecord, material::{HenyeyGreenstein, Material}, microfacet::fresnel_dielectric, rand::random_f64, ray::Ray, vec3::{reflect, refract, Vec3}};
+
+/// Single scattering albedo that makes a thick slab of the medium look like `albedo` after all the
+/// bounces inside, from the fit in "Practical and Controllable Subsurface Scattering" (Chiang et al. 2016)
+fn single_scattering_albedo(albedo: f64) -> f64 {
+	let a = albedo.clamp(0.0, 0.999);
+	let s = 4.09712 + 4.20863 * a - f64::sqrt(9.59217 + 41.6808 * a + 17.7126 * a * a);
+	(1.0 - s * s).clamp(0.0, 1.0)
+}
+
+/// Translucent materials like skin, wax, marble or milk, where light enters the surface, bounces around
+/// inside and comes out somewhere else. Rays refract into the object and do a random walk through its
+/// interior as a scattering medium until they find their way out through the boundary again.
+///
+/// The object needs to be closed, since the walk relies on `front_face` to tell entering from being inside.
+/// Every step of the walk counts as a bounce, so dense media with short mean free paths need a higher `max_depth`.
+pub struct Subsurface {
+	// Extinction coefficient per channel, one over the mean free path
+	sigma_t: Color,
+	// Chance per channel that light is scattered rather than absorbed at each step
+	albedo: Color,
+	ior: f64,
+	phase: HenyeyGreenstein,
+}
+
+impl Subsurface {
+	/// `albedo` is the color the material ends up looking like, `mean_free_path` is how far light
+	/// travels on average between scattering events, per channel in scene units.
+	/// Red usually gets the longest path, e.g skin is around (1.0, 0.2, 0.1) times the scale of a face in centimeters.
+	pub fn new(albedo: Color, mean_free_path: Color, ior: f64) -> Self {
+		Self::anisotropic(albedo, mean_free_path, ior, 0.0)
+	}
+
+	/// Like `new`, with a Henyey-Greenstein phase function of asymmetry `g` for the scattering inside.
+	/// Tissue scatters strongly forward, around 0.8.
+	pub fn anisotropic(albedo: Color, mean_free_path: Color, ior: f64, g: f64) -> Self {
+		let sigma = |mfp: f64| 1.0/f64::max(mfp, 1e-6);
+		Self {
+			sigma_t: Color::from_xyz(sigma(mean_free_path.x()), sigma(mean_free_path.y()), sigma(mean_free_path.z())),
+			albedo: Color::from_xyz(single_scattering_albedo(albedo.x()), single_scattering_albedo(albedo.y()), single_scattering_albedo(albedo.z())),
+			ior,
+			phase: HenyeyGreenstein::new(Color::from_xyz(1.0, 1.0, 1.0), g),
+		}
+	}
+
+	fn transmittance(&self, distance: f64) -> Color {
+		Color::from_xyz(
+			f64::exp(-self.sigma_t.x() * distance),
+			f64::exp(-self.sigma_t.y() * distance),
+			f64::exp(-self.sigma_t.z() * distance),
+		)
+	}
+
+	/// Reflects or refracts at the smooth boundary by its Fresnel reflectance
+	fn boundary(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+		let eta = if rec.front_face {self.ior} else {1.0/self.ior};
+		let unit_direction = Vec3::unit_vector(&r_in.direction());
+		let cos_theta = f64::min(Vec3::dot(&-unit_direction, &rec.normal), 1.0);
+
+		if random_f64() < fresnel_dielectric(cos_theta, eta) {
+			reflect(&unit_direction, &rec.normal)
+		} else {
+			refract(&unit_direction, &rec.normal, 1.0/eta)
+		}
+	}
+}
+
+impl Material for Subsurface {
+	fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+		if rec.front_face {
+			*attenuation = Color::from_xyz(1.0, 1.0, 1.0);
+			*scattered = Ray::from_values(&rec.p, &self.boundary(r_in, rec));
+			return true
+		}
+
+		// Hitting the inside of the surface means the ray is in the medium. Sample how far it gets before
+		// scattering with one channel's coefficient, and weigh by the density of all three to stay unbiased.
+		let distance = rec.t * r_in.direction().length();
+		let channel = ((random_f64() * 3.0) as usize).min(2);
+		let t = -f64::ln(1.0 - random_f64())/self.sigma_t[channel];
+
+		if t < distance {
+			let transmittance = self.transmittance(t);
+			let pdf = Vec3::dot(&self.sigma_t, &transmittance)/3.0;
+			if pdf <= 0.0 {
+				return false
+			}
+
+			let mut inside = rec.clone();
+			inside.p = r_in.origin() + t * Vec3::unit_vector(&r_in.direction());
+			let mut phase_attenuation = Color::new();
+			self.phase.scatter(r_in, &inside, &mut phase_attenuation, scattered);
+			*attenuation = self.albedo * self.sigma_t * transmittance/pdf;
+			return true
+		}
+
+		// Made it to the boundary without scattering
+		let transmittance = self.transmittance(distance);
+		let p_pass = (transmittance.x() + transmittance.y() + transmittance.z())/3.0;
+		if p_pass <= 0.0 {
+			return false
+		}
+		*attenuation = transmittance/p_pass;
+		*scattered = Ray::from_values(&rec.p, &self.boundary(r_in, rec));
+		true
+	}
+}
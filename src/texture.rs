@@ -0,0 +1,250 @@
+use std::{io, path::Path, sync::Arc};
+
+use crate::{color::Color, image::Image, noise::{Perlin, Worley}, vec3::Point3};
+
+/// A color that varies over a surface, looked up by texture coordinates (u, v) or by the hit point itself
+pub trait Texture: Send + Sync {
+	fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+	/// Opacity from 0 to 1, for textures that can be see-through like images with an alpha channel
+	fn alpha(&self, _u: f64, _v: f64, _p: &Point3) -> f64 {
+		1.0
+	}
+}
+
+pub struct SolidColor {
+	albedo: Color
+}
+
+impl SolidColor {
+	pub fn new(albedo: Color) -> Self {
+		Self { albedo }
+	}
+
+	pub fn from_rgb(red: f64, green: f64, blue: f64) -> Self {
+		Self::new(Color::from_xyz(red, green, blue))
+	}
+}
+
+impl Texture for SolidColor {
+	fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+		self.albedo
+	}
+}
+
+/// Where a checker pattern is laid out
+#[derive(Clone, Copy)]
+pub enum CheckerMapping {
+	// 3D checks in world space, the pattern doesn't depend on the shape
+	Spatial,
+	// 2D checks over the surface's texture coordinates
+	Uv
+}
+
+/// Alternates between two textures in squares `scale` wide, in world units or texture coordinates
+pub struct CheckerTexture {
+	inv_scale: f64,
+	even: Arc<dyn Texture>,
+	odd: Arc<dyn Texture>,
+	mapping: CheckerMapping
+}
+
+impl CheckerTexture {
+	pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>, mapping: CheckerMapping) -> Self {
+		Self { inv_scale: 1.0/scale, even, odd, mapping }
+	}
+
+	pub fn from_colors(scale: f64, even: Color, odd: Color, mapping: CheckerMapping) -> Self {
+		Self::new(scale, Arc::new(SolidColor::new(even)), Arc::new(SolidColor::new(odd)), mapping)
+	}
+}
+
+impl Texture for CheckerTexture {
+	fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+		let cell = |x: f64| (self.inv_scale * x).floor() as i64;
+		let sum = match self.mapping {
+			CheckerMapping::Spatial => cell(p.x()) + cell(p.y()) + cell(p.z()),
+			CheckerMapping::Uv => cell(u) + cell(v),
+		};
+
+		if sum.rem_euclid(2) == 0 {self.even.value(u, v, p)} else {self.odd.value(u, v, p)}
+	}
+}
+
+/// What happens to texture coordinates outside of [0, 1]
+#[derive(Clone, Copy)]
+pub enum WrapMode {
+	Repeat,
+	Clamp,
+	Mirror
+}
+
+impl WrapMode {
+	// Maps any texel index into 0..n
+	fn wrap(&self, i: i64, n: usize) -> usize {
+		let n = n as i64;
+		let i = match self {
+			WrapMode::Repeat => i.rem_euclid(n),
+			WrapMode::Clamp => i.clamp(0, n - 1),
+			WrapMode::Mirror => {
+				let m = i.rem_euclid(2 * n);
+				if m >= n {2 * n - 1 - m} else {m}
+			}
+		};
+		i as usize
+	}
+}
+
+/// An image stretched over the texture coordinates, with (0, 0) at the bottom left, filtered bilinearly
+pub struct ImageTexture {
+	image: Image,
+	wrap: WrapMode
+}
+
+impl ImageTexture {
+	pub fn new(image: Image, wrap: WrapMode) -> Self {
+		Self { image, wrap }
+	}
+
+	pub fn load(path: impl AsRef<Path>, wrap: WrapMode) -> io::Result<Self> {
+		Ok(Self::new(Image::load(path)?, wrap))
+	}
+
+	/// Bilinearly filtered lookup of whatever `texel` returns for a pixel
+	fn filter(&self, u: f64, v: f64, texel: impl Fn(usize, usize) -> Color) -> Color {
+		// Image rows go top to bottom, so flip v. Texel centers sit half a texel in.
+		let x = u * self.image.width as f64 - 0.5;
+		let y = (1.0 - v) * self.image.height as f64 - 0.5;
+		let (x0, y0) = (x.floor(), y.floor());
+		let (fx, fy) = (x - x0, y - y0);
+
+		let at = |dx: i64, dy: i64| {
+			let i = self.wrap.wrap(x0 as i64 + dx, self.image.width);
+			let j = self.wrap.wrap(y0 as i64 + dy, self.image.height);
+			texel(i, j)
+		};
+
+		let top = (1.0 - fx) * at(0, 0) + fx * at(1, 0);
+		let bottom = (1.0 - fx) * at(0, 1) + fx * at(1, 1);
+		(1.0 - fy) * top + fy * bottom
+	}
+}
+
+impl Texture for ImageTexture {
+	fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+		// Solid cyan as a debugging aid for a missing image
+		if self.image.width == 0 || self.image.height == 0 {
+			return Color::from_xyz(0.0, 1.0, 1.0)
+		}
+
+		self.filter(u, v, |x, y| self.image.pixel(x, y))
+	}
+
+	fn alpha(&self, u: f64, v: f64, _p: &Point3) -> f64 {
+		if !self.image.has_alpha() || self.image.width == 0 || self.image.height == 0 {
+			return 1.0
+		}
+
+		self.filter(u, v, |x, y| {
+			let a = self.image.alpha(x, y);
+			Color::from_xyz(a, a, a)
+		}).x()
+	}
+}
+
+/// How a `NoiseTexture` turns Perlin noise into a blend between its two colors
+#[derive(Clone, Copy)]
+pub enum NoisePattern {
+	// Plain, smooth noise
+	Noise,
+	// Billowy, like smoke or clouds
+	Turbulence,
+	// Layered noise with detail at every scale, like terrain
+	Fbm,
+	// Veins along z, bent by turbulence
+	Marble,
+	// Rings around the y axis, bent by turbulence
+	Wood
+}
+
+/// A procedural texture from seeded Perlin noise, evaluated in world space with features about `1/scale` wide
+pub struct NoiseTexture {
+	noise: Perlin,
+	scale: f64,
+	pattern: NoisePattern,
+	low: Color,
+	high: Color
+}
+
+impl NoiseTexture {
+	pub fn new(seed: u32, scale: f64, pattern: NoisePattern) -> Self {
+		Self::from_colors(seed, scale, pattern, Color::new(), Color::from_xyz(1.0, 1.0, 1.0))
+	}
+
+	pub fn from_colors(seed: u32, scale: f64, pattern: NoisePattern, low: Color, high: Color) -> Self {
+		Self { noise: Perlin::new(seed), scale, pattern, low, high }
+	}
+}
+
+impl Texture for NoiseTexture {
+	fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+		let sp = self.scale * *p;
+		let t = match self.pattern {
+			NoisePattern::Noise => 0.5 * (1.0 + self.noise.noise(&sp)),
+			NoisePattern::Turbulence => self.noise.turbulence(&sp, 7),
+			NoisePattern::Fbm => 0.5 * (1.0 + self.noise.fbm(&sp, 6, 2.0, 0.5)),
+			NoisePattern::Marble => 0.5 * (1.0 + f64::sin(sp.z() + 10.0 * self.noise.turbulence(p, 7))),
+			NoisePattern::Wood => {
+				let rings = f64::sqrt(sp.x() * sp.x() + sp.z() * sp.z()) + 2.0 * self.noise.turbulence(p, 4);
+				rings - rings.floor()
+			}
+		};
+
+		let t = t.clamp(0.0, 1.0);
+		(1.0 - t) * self.low + t * self.high
+	}
+}
+
+/// What a `WorleyTexture` shows of the distances to the nearest cell feature points
+#[derive(Clone, Copy)]
+pub enum CellPattern {
+	// Distance to the closest point, dark spots in bright cells
+	F1,
+	// Distance to the second closest point
+	F2,
+	// F2 - F1, dark lines along the cell borders like cracked mud or stone tiles
+	Edges
+}
+
+/// A procedural texture from seeded Worley (cellular) noise, in world space with cells about `1/scale` wide
+pub struct WorleyTexture {
+	noise: Worley,
+	scale: f64,
+	pattern: CellPattern,
+	low: Color,
+	high: Color
+}
+
+impl WorleyTexture {
+	pub fn new(seed: u32, scale: f64, pattern: CellPattern) -> Self {
+		Self::from_colors(seed, scale, pattern, Color::new(), Color::from_xyz(1.0, 1.0, 1.0))
+	}
+
+	pub fn from_colors(seed: u32, scale: f64, pattern: CellPattern, low: Color, high: Color) -> Self {
+		Self { noise: Worley::new(seed), scale, pattern, low, high }
+	}
+}
+
+impl Texture for WorleyTexture {
+	fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+		let (f1, f2) = self.noise.distances(&(self.scale * *p));
+		let t = match self.pattern {
+			CellPattern::F1 => f1,
+			CellPattern::F2 => f2,
+			CellPattern::Edges => 4.0 * (f2 - f1),
+		};
+
+		let t = t.clamp(0.0, 1.0);
+		(1.0 - t) * self.low + t * self.high
+	}
+}
@@ -0,0 +1,115 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{color::Color, hittable::HitRecord, spectrum::{rgb_to_spectrum, sample_to_rgb, LAMBDA_MAX, LAMBDA_MIN}, texture::{SolidColor, Texture}};
+
+// Wavelengths the reflectance is averaged over when rendering in RGB
+const RGB_WAVELENGTHS: usize = 16;
+
+/// Amplitude reflection coefficients (s and p polarized) going from index `n_i` into `n_t`,
+/// or None on total internal reflection
+fn fresnel_amplitudes(cos_i: f64, n_i: f64, n_t: f64) -> Option<(f64, f64, f64)> {
+	let sin2_t = (n_i/n_t) * (n_i/n_t) * f64::max(0.0, 1.0 - cos_i * cos_i);
+	if sin2_t >= 1.0 {
+		return None
+	}
+	let cos_t = f64::sqrt(1.0 - sin2_t);
+
+	let rs = (n_i * cos_i - n_t * cos_t)/(n_i * cos_i + n_t * cos_t);
+	let rp = (n_t * cos_i - n_i * cos_t)/(n_t * cos_i + n_i * cos_t);
+	Some((rs, rp, cos_t))
+}
+
+/// Reflectance of two interfaces with real amplitude coefficients r12 and r23 whose reflections
+/// are out of phase by `phase`, interfering like in an Airy/Fabry-Perot etalon
+fn airy(r12: f64, r23: f64, phase: f64) -> f64 {
+	let c = 2.0 * r12 * r23 * phase.cos();
+	(r12 * r12 + r23 * r23 + c)/(1.0 + r12 * r12 * r23 * r23 + c)
+}
+
+/// A thin transparent layer on top of a surface, e.g soap, oil or an anti-reflective coating.
+/// Light reflecting off its top and bottom interferes, so the reflectance depends on the wavelength,
+/// the viewing angle and the layer's thickness, which gives the familiar iridescent colors.
+pub struct ThinFilm {
+	ior: f64,
+	// The average of the texture's channels picks the thickness between the min and max, in nanometers
+	thickness: Arc<dyn Texture>,
+	min_thickness: f64,
+	max_thickness: f64,
+}
+
+impl ThinFilm {
+	/// A film of even `thickness` in nanometers
+	pub fn new(ior: f64, thickness: f64) -> Self {
+		Self::from_texture(ior, Arc::new(SolidColor::from_rgb(1.0, 1.0, 1.0)), thickness, thickness)
+	}
+
+	/// A film whose thickness varies over the surface, from `min_thickness` where the texture is black
+	/// to `max_thickness` where it's white, in nanometers
+	pub fn from_texture(ior: f64, thickness: Arc<dyn Texture>, min_thickness: f64, max_thickness: f64) -> Self {
+		Self { ior, thickness, min_thickness: f64::max(0.0, min_thickness), max_thickness: f64::max(0.0, max_thickness) }
+	}
+
+	fn thickness_at(&self, rec: &HitRecord) -> f64 {
+		let c = self.thickness.value(rec.u, rec.v, &rec.p);
+		let t = ((c.x() + c.y() + c.z())/3.0).clamp(0.0, 1.0);
+		self.min_thickness + t * (self.max_thickness - self.min_thickness)
+	}
+
+	/// Phase difference between the reflections off the film's top and bottom, and the cosine inside the film.
+	/// None if light can't enter the film at this angle.
+	fn phase(&self, cos_i: f64, n_i: f64, thickness: f64, wavelength: f64) -> Option<(f64, f64)> {
+		let (_, _, cos_film) = fresnel_amplitudes(cos_i, n_i, self.ior)?;
+		Some((4.0 * PI * self.ior * thickness * cos_film/wavelength, cos_film))
+	}
+
+	/// Turns a reflectance at one wavelength into a color, either for the ray's own wavelength in spectral mode
+	/// or averaged over the visible range in RGB mode
+	fn reflectance_color(wavelength: Option<f64>, reflectance: impl Fn(f64) -> f64) -> Color {
+		if let Some(wavelength) = wavelength {
+			let r = reflectance(wavelength);
+			return Color::from_xyz(r, r, r)
+		}
+
+		let step = (LAMBDA_MAX - LAMBDA_MIN)/RGB_WAVELENGTHS as f64;
+		let mut sum = Color::new();
+		for i in 0..RGB_WAVELENGTHS {
+			let wavelength = LAMBDA_MIN + (i as f64 + 0.5) * step;
+			sum += sample_to_rgb(reflectance(wavelength), wavelength, 1.0/(LAMBDA_MAX - LAMBDA_MIN));
+		}
+		let rgb = sum/RGB_WAVELENGTHS as f64;
+		Color::from_xyz(rgb.x().clamp(0.0, 1.0), rgb.y().clamp(0.0, 1.0), rgb.z().clamp(0.0, 1.0))
+	}
+
+	/// Reflectance of the coated boundary between indices `n_i`, where the light comes from, and `n_t`.
+	/// Whatever isn't reflected is transmitted, the film doesn't absorb anything.
+	pub fn reflectance(&self, rec: &HitRecord, cos_i: f64, n_i: f64, n_t: f64, wavelength: Option<f64>) -> Color {
+		let thickness = self.thickness_at(rec);
+
+		Self::reflectance_color(wavelength, |wavelength| {
+			let Some((phase, cos_film)) = self.phase(cos_i, n_i, thickness, wavelength) else {
+				return 1.0
+			};
+			let (Some((rs12, rp12, _)), Some((rs23, rp23, _))) = (fresnel_amplitudes(cos_i, n_i, self.ior), fresnel_amplitudes(cos_film, self.ior, n_t)) else {
+				return 1.0
+			};
+			(airy(rs12, rs23, phase) + airy(rp12, rp23, phase))/2.0
+		})
+	}
+
+	/// Reflectance of the film on top of a mirror-like surface with the given reflectance, e.g a metal.
+	/// The mirror is treated as flipping the phase of the light, like a perfect conductor does.
+	pub fn reflectance_over_mirror(&self, rec: &HitRecord, cos_i: f64, base: &Color, wavelength: Option<f64>) -> Color {
+		let thickness = self.thickness_at(rec);
+
+		Self::reflectance_color(wavelength, |wavelength| {
+			let Some((phase, _)) = self.phase(cos_i, 1.0, thickness, wavelength) else {
+				return 1.0
+			};
+			let Some((rs12, rp12, _)) = fresnel_amplitudes(cos_i, 1.0, self.ior) else {
+				return 1.0
+			};
+			let r23 = -rgb_to_spectrum(base, wavelength).clamp(0.0, 1.0).sqrt();
+			(airy(rs12, r23, phase) + airy(rp12, r23, phase))/2.0
+		})
+	}
+}
@@ -0,0 +1,156 @@
+use crate::{camera::Camera, color::Color, film::Film, hittable::{HitRecord, Hittable}, integrator::{Integrator, T_MIN}, interval::Interval, rand::random_f64, ray::Ray, scene::Scene, vec3::Vec3};
+
+/// What the edge detection looks at for one point of the image: the depth and normal AOVs of the first hit
+struct GSample {
+	hit: bool,
+	depth: f64,
+	normal: Vec3,
+}
+
+impl GSample {
+	fn trace(r: &Ray, scene: &Scene) -> (Self, HitRecord) {
+		let mut rec = HitRecord::new();
+		let hit = scene.world.hit(r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec);
+		(Self { hit, depth: rec.t * r.direction().length(), normal: rec.normal }, rec)
+	}
+}
+
+/// Non-photorealistic rendering for technical illustrations: flat cel shaded bands lit by a single directional
+/// light, ink lines along silhouettes and creases, and optional cross hatching in the shadows.
+/// Create it with `new` and adjust the fields.
+///
+/// Edges are found per sample by tracing a small cross of extra rays `edge_width` pixels around it and comparing
+/// their depth and normals, so they get antialiased along with everything else as samples accumulate.
+pub struct Toon {
+	// Direction towards the light, doesn't need to be normalized
+	pub light_direction: Vec3,
+	// Number of lit shades, not counting the unlit one
+	pub bands: i32,
+	// Whether surfaces facing the light can be in the shadow of others
+	pub shadows: bool,
+	// Brightness of the unlit band, from 0 to 1
+	pub ambient: f64,
+	pub edge_color: Color,
+	// Width of the ink lines in pixels, 0 turns them off
+	pub edge_width: f64,
+	// Silhouette where the depth bends away from a straight line by more than this fraction of the distance
+	pub depth_threshold: f64,
+	// Crease where the surface bends by more than this angle between neighbouring samples, in degrees
+	pub crease_angle: f64,
+	// Hatch lines in the darker bands, getting denser with cross hatching as it gets darker
+	pub hatching: bool,
+	// Distance between hatch lines in pixels
+	pub hatch_spacing: f64,
+}
+
+impl Default for Toon {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Toon {
+	pub fn new() -> Self {
+		Self {
+			light_direction: Vec3::from_xyz(1.0, 2.0, 1.0),
+			bands: 3,
+			shadows: true,
+			ambient: 0.25,
+			edge_color: Color::new(),
+			edge_width: 1.0,
+			depth_threshold: 0.05,
+			crease_angle: 30.0,
+			hatching: false,
+			hatch_spacing: 6.0,
+		}
+	}
+
+	/// Whether the sample at `center` is on an edge, given its neighbours left, right, above and below
+	fn is_edge(&self, center: &GSample, neighbours: &[GSample; 4]) -> bool {
+		if neighbours.iter().any(|n| n.hit != center.hit) {
+			return true
+		}
+		if !center.hit {
+			return false
+		}
+
+		// Depth changes linearly across a flat surface, so look at how much it curves instead of its gradient
+		let [left, right, up, down] = neighbours;
+		let curvature = f64::max((left.depth + right.depth - 2.0 * center.depth).abs(), (up.depth + down.depth - 2.0 * center.depth).abs());
+		if curvature > self.depth_threshold * center.depth {
+			return true
+		}
+
+		let cos_crease = self.crease_angle.to_radians().cos();
+		neighbours.iter().any(|n| Vec3::dot(&n.normal, &center.normal) < cos_crease)
+	}
+
+	/// Flat shade from 0 to 1 for the lit part of a surface, in `bands` even steps
+	fn band(&self, rec: &HitRecord, scene: &Scene) -> f64 {
+		let to_light = Vec3::unit_vector(&self.light_direction);
+		let mut lit = f64::max(0.0, Vec3::dot(&rec.normal, &to_light));
+		if lit > 0.0 && self.shadows {
+			let mut blocker = HitRecord::new();
+			if scene.world.hit_solid(&Ray::from_values(&rec.p, &to_light), &Interval::from_values(T_MIN, f64::INFINITY), &mut blocker) {
+				lit = 0.0;
+			}
+		}
+
+		let bands = self.bands.max(1) as f64;
+		(lit * bands).ceil()/bands
+	}
+
+	/// Flat color of a hit: whatever the material would tint light with, in the hit's band. Lights show their own color.
+	fn shade(&self, r: &Ray, rec: &HitRecord, scene: &Scene) -> Color {
+		let mut scattered = Ray::new();
+		let mut tone = Color::new();
+		if !rec.mat.scatter(r, rec, &mut tone, &mut scattered) {
+			return rec.mat.emitted(r, rec)
+		}
+		let tone = Color::from_xyz(tone.x().clamp(0.0, 1.0), tone.y().clamp(0.0, 1.0), tone.z().clamp(0.0, 1.0));
+
+		(self.ambient + (1.0 - self.ambient) * self.band(rec, scene)) * tone
+	}
+
+	/// Whether a point of the image at darkness `dark` from 0 to 1 falls on a hatch line
+	fn is_hatched(&self, x: f64, y: f64, dark: f64) -> bool {
+		let spacing = f64::max(self.hatch_spacing, 1.0);
+		let line = |t: f64| t.rem_euclid(spacing) < 0.25 * spacing;
+		(dark > 0.3 && line(x + y)) || (dark > 0.6 && line(x - y))
+	}
+}
+
+impl Integrator for Toon {
+	/// The cel shaded color of the first hit, without any lines
+	fn ray_color(&self, r: &Ray, _depth: i32, scene: &Scene) -> Color {
+		let mut rec = HitRecord::new();
+		if !scene.world.hit(r, &Interval::from_values(T_MIN, f64::INFINITY), &mut rec) {
+			return scene.background(r)
+		}
+		self.shade(r, &rec, scene)
+	}
+
+	fn sample_pixel(&self, cam: &Camera, i: i32, j: i32, scene: &Scene, _film: &Film) -> Color {
+		let x = i as f64 + random_f64() - 0.5;
+		let y = j as f64 + random_f64() - 0.5;
+		let r = cam.get_ray_at(x, y);
+		let (center, rec) = GSample::trace(&r, scene);
+
+		if self.edge_width > 0.0 {
+			let w = self.edge_width;
+			let neighbours = [(-w, 0.0), (w, 0.0), (0.0, -w), (0.0, w)]
+				.map(|(dx, dy)| GSample::trace(&cam.get_ray_at(x + dx, y + dy), scene).0);
+			if self.is_edge(&center, &neighbours) {
+				return self.edge_color
+			}
+		}
+
+		if !center.hit {
+			return scene.background(&r)
+		}
+		if self.hatching && self.is_hatched(x, y, 1.0 - self.band(&rec, scene)) {
+			return self.edge_color
+		}
+		self.shade(&r, &rec, scene)
+	}
+}
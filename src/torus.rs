@@ -0,0 +1,134 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{hittable::{HitRecord, Hittable}, interval::Interval, material::Material, onb::Onb, polynomial::roots_in, ray::Ray, vec3::{Point3, Vec3}};
+
+/// A ring around `axis` through `center`, e.g a donut, a tire or a ring buoy. `major_radius` is from the center
+/// to the middle of the tube, `minor_radius` is the tube's own.
+/// Texture coordinates wrap u once around the axis and v once around the tube, starting on the outside.
+pub struct Torus {
+	center: Point3,
+	major_radius: f64,
+	minor_radius: f64,
+	mat: Arc<dyn Material>,
+	// Unit axis and two unit axes across it, with tangent × bitangent = w
+	w: Vec3,
+	tangent: Vec3,
+	bitangent: Vec3,
+}
+
+impl Torus {
+	pub fn new(center: &Point3, axis: &Vec3, major_radius: f64, minor_radius: f64, mat: Arc<dyn Material>) -> Self {
+		let w = Vec3::unit_vector(axis);
+		let tangent = Onb::new(&w).u();
+		Self {
+			center: *center,
+			major_radius: f64::max(0.0, major_radius),
+			minor_radius: f64::max(0.0, minor_radius),
+			mat,
+			w,
+			tangent,
+			bitangent: Vec3::cross(&w, &tangent),
+		}
+	}
+
+	/// A point relative to the center in the torus' frame, where the axis is z
+	fn to_local(&self, v: &Vec3) -> Vec3 {
+		Vec3::from_xyz(Vec3::dot(v, &self.tangent), Vec3::dot(v, &self.bitangent), Vec3::dot(v, &self.w))
+	}
+
+	/// Where the ray is inside the torus' bounding sphere, if it gets there at all
+	fn bounds(&self, r: &Ray) -> Option<(f64, f64)> {
+		let radius = self.major_radius + self.minor_radius;
+		let oc = self.center - r.origin();
+		let a = r.direction().length_squared();
+		let h = Vec3::dot(&r.direction(), &oc);
+		let c = oc.length_squared() - radius * radius;
+		let discriminant = h * h - a * c;
+		if discriminant < 0.0 {
+			return None
+		}
+		let sqrtd = discriminant.sqrt();
+		Some(((h - sqrtd)/a, (h + sqrtd)/a))
+	}
+}
+
+impl Hittable for Torus {
+	fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
+		let Some((enter, exit)) = self.bounds(r) else {
+			return false
+		};
+		let (start, end) = (f64::max(ray_t.min, enter), f64::min(ray_t.max, exit));
+		if start > end {
+			return false
+		}
+
+		// (x² + y² + z² + R² - r²)² = 4R²(x² + y²) along the ray, starting from where it enters the bounds
+		// so the coefficients stay small and the roots keep their digits
+		let o = self.to_local(&(r.at(start) - self.center));
+		let d = self.to_local(&r.direction());
+		let (big, small) = (self.major_radius, self.minor_radius);
+		let dd = d.length_squared();
+		let od = Vec3::dot(&o, &d);
+		let k = o.length_squared() + big * big - small * small;
+		let four_r2 = 4.0 * big * big;
+		let coefficients = [
+			k * k - four_r2 * (o.x() * o.x() + o.y() * o.y()),
+			4.0 * od * k - 2.0 * four_r2 * (o.x() * d.x() + o.y() * d.y()),
+			4.0 * od * od + 2.0 * dd * k - four_r2 * (d.x() * d.x() + d.y() * d.y()),
+			4.0 * dd * od,
+			dd * dd,
+		];
+
+		for s in roots_in(&coefficients, 0.0, end - start) {
+			let t = start + s;
+			if !ray_t.surrounds(t) {
+				continue
+			}
+
+			rec.t = t;
+			rec.p = r.at(t);
+			let local = o + s * d;
+			let rho = f64::sqrt(local.x() * local.x() + local.y() * local.y());
+			let radial = if rho > 1e-9 {(local.x() * self.tangent + local.y() * self.bitangent)/rho} else {self.tangent};
+
+			// Away from the nearest point on the ring through the middle of the tube
+			let phi = f64::atan2(local.y(), local.x()).rem_euclid(2.0 * PI);
+			let theta = f64::atan2(local.z(), rho - big).rem_euclid(2.0 * PI);
+			let outward_normal = theta.cos() * radial + theta.sin() * self.w;
+			(rec.u, rec.v) = (phi/(2.0 * PI), theta/(2.0 * PI));
+			rec.tangent = 2.0 * PI * (-local.y() * self.tangent + local.x() * self.bitangent);
+			rec.bitangent = 2.0 * PI * small * (-theta.sin() * radial + theta.cos() * self.w);
+			rec.set_face_normal(r, &outward_normal);
+			rec.mat = self.mat.clone();
+
+			if !rec.is_cut_out(r) {
+				return true
+			}
+		}
+
+		false
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::material::blank_material;
+
+	#[test]
+	fn edge_on_hits() {
+		let torus = Torus::new(&Point3::new(), &Vec3::from_xyz(0.0, 1.0, 0.0), 2.0, 0.5, blank_material());
+		let r = Ray::from_values(&Point3::from_xyz(-100.0, 0.0, 0.0), &Vec3::from_xyz(1.0, 0.0, 0.0));
+
+		// Through both sides of the tube on the way in and again on the way out
+		let mut t_min = 0.001;
+		for expected in [97.5, 98.5, 101.5, 102.5] {
+			let mut rec = HitRecord::new();
+			assert!(torus.hit(&r, &Interval::from_values(t_min, f64::INFINITY), &mut rec));
+			assert!((rec.t - expected).abs() < 1e-9, "hit at {}, expected {expected}", rec.t);
+			assert!(rec.normal.y().abs() < 1e-9);
+			t_min = rec.t + 1e-6;
+		}
+		assert!(!torus.hit(&r, &Interval::from_values(t_min, f64::INFINITY), &mut HitRecord::new()));
+	}
+}
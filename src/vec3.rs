@@ -262,4 +262,16 @@ pub fn random_in_unit_disk() -> Vec3 {
             return p;
         }
     }
-}
\ No newline at end of file
+}
+/// Returns a random direction around +z, distributed proportionally to cos(theta)
+pub fn random_cosine_direction() -> Vec3 {
+    let r1 = random_f64();
+    let r2 = random_f64();
+
+    let phi = 2.0 * f64::consts::PI * r1;
+    let x = f64::cos(phi) * r2.sqrt();
+    let y = f64::sin(phi) * r2.sqrt();
+    let z = (1.0 - r2).sqrt();
+
+    Vec3::from_xyz(x, y, z)
+}